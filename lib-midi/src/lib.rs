@@ -3,9 +3,11 @@ pub mod playback;
 mod track;
 mod utils;
 mod metronome;
+mod gm;
+mod practice;
 
 pub use midly;
-pub use {midi::*, playback::*, track::*, utils::*};
+pub use {gm::*, metronome::*, midi::*, playback::*, practice::*, track::*, utils::*};
 
 #[cfg(test)]
 mod tests {
@@ -1,100 +1,144 @@
-use midly::{MidiMessage, num::u7};
-
-use crate::{pulses_to_duration, MidiEvent, MidiTrack, TempoTrack};
-
-pub fn add_metronome(
-    mut merged_track: MidiTrack,
-    tempo_events: &TempoTrack,
-    pulses_per_quarter_note: u16,
-) -> MidiTrack {
-    let beats_per_measure = 4;
-
-    let mut events = Vec::new();
-    let mut pulses = 0u64;
-
-    let track_start = merged_track.events.first().unwrap().timestamp;
-    let track_end = merged_track.events.last().unwrap().timestamp;
-    let mut timestamp = track_start;
-
-    let track_id = 99;
-    let channel = 15;
-    let delta = 0;
-    let accent_key: u7 =  64.into();
-    let regular_key: u7 = 62.into();
-    let wood_block: u7 = 115.into();
-
-    events.push(MidiEvent {
-        channel,
-        delta,
-        timestamp,
-        message: MidiMessage::ProgramChange { program: wood_block },
-        track_id,
-    });
-
-    for i in 0..i32::MAX {
-        if i % beats_per_measure == 0 {
-            events.push(MidiEvent {
-                channel,
-                delta,
-                timestamp,
-                message: MidiMessage::NoteOn {
-                    key: accent_key,
-                    vel: 127.into(),
-                },
-                track_id,
-            });
-            events.push(MidiEvent {
-                channel,
-                delta,
-                timestamp,
-                message: MidiMessage::NoteOff {
-                    key: accent_key,
-                    vel: 0.into(),
-                },
-                track_id,
-            });
-        } else {
-            events.push(MidiEvent {
-                channel,
-                delta,
-                timestamp,
-                message: MidiMessage::NoteOn {
-                    key: regular_key,
-                    vel: 80.into(),
-                },
-                track_id,
-            });
-            events.push(MidiEvent {
-                channel,
-                delta,
-                timestamp,
-                message: MidiMessage::NoteOff {
-                    key: regular_key,
-                    vel: 0.into(),
-                },
-                track_id,
-            });
+use std::time::Duration;
+
+use midly::{num::u7, MidiMessage};
+
+use crate::{pulses_to_duration, MidiTrack, TempoEvent};
+
+const ACCENT_VELOCITY: u8 = 127;
+const BEAT_VELOCITY: u8 = 80;
+const SUBDIVISION_VELOCITY: u8 = 55;
+
+/// How the metronome clicks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetronomeSettings {
+    /// 1 clicks quarter notes, 2 eighth notes, 3 triplets, 4 sixteenth notes.
+    pub clicks_per_beat: u32,
+    /// One entry per beat of a bar, accented beats play `accent_key`.
+    pub accents: Vec<bool>,
+    pub program: u8,
+    pub accent_key: u8,
+    pub regular_key: u8,
+    /// Second voice of the practice metronome polyrhythms.
+    pub counter_key: u8,
+}
+
+impl Default for MetronomeSettings {
+    fn default() -> Self {
+        Self {
+            clicks_per_beat: 2,
+            accents: vec![true, false, false, false],
+            // Woodblock
+            program: 115,
+            accent_key: 64,
+            regular_key: 62,
+            counter_key: 69,
         }
+    }
+}
+
+impl MetronomeSettings {
+    pub fn beats_per_bar(&self) -> usize {
+        self.accents.len().max(1)
+    }
+
+    /// Kind of the click played on `beat` (counted from the start of a bar),
+    /// `on_beat` is false for the clicks of the subdivision in between.
+    pub fn click_kind(&self, beat: usize, on_beat: bool) -> ClickKind {
+        let accent = self.accents.get(beat % self.beats_per_bar()) == Some(&true);
 
-        pulses += pulses_per_quarter_note as u64 / 2;
-        timestamp = pulses_to_duration(tempo_events, pulses, pulses_per_quarter_note);
-        if timestamp >= track_end {
-            break;
+        match (on_beat, accent) {
+            (true, true) => ClickKind::Accent,
+            (true, false) => ClickKind::Beat,
+            (false, _) => ClickKind::Subdivision,
         }
     }
 
-    let metronome = MidiTrack {
-        notes: Vec::with_capacity(0),
-        events,
-        track_id,
-    };
+    /// Note on and off of a click.
+    pub fn click_events(&self, kind: ClickKind) -> [MidiMessage; 2] {
+        let (key, vel) = match kind {
+            ClickKind::Accent => (self.accent_key, ACCENT_VELOCITY),
+            ClickKind::Beat => (self.regular_key, BEAT_VELOCITY),
+            ClickKind::Subdivision => (self.regular_key, SUBDIVISION_VELOCITY),
+            ClickKind::Counter => (self.counter_key, BEAT_VELOCITY),
+        };
+        let key = u7::from_int_lossy(key);
 
-    for n in metronome.notes.iter().cloned() {
-        merged_track.notes.push(n);
+        [
+            MidiMessage::NoteOn {
+                key,
+                vel: u7::from_int_lossy(vel),
+            },
+            MidiMessage::NoteOff { key, vel: 0.into() },
+        ]
     }
-    for e in metronome.events.iter().cloned() {
-        merged_track.events.push(e);
+
+    /// Selects the click sound.
+    pub fn program_change(&self) -> MidiMessage {
+        MidiMessage::ProgramChange {
+            program: u7::from_int_lossy(self.program),
+        }
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickKind {
+    /// First beat of a bar, or any beat marked in the accents.
+    Accent,
+    Beat,
+    /// Click between two beats.
+    Subdivision,
+    /// Second voice of a polyrhythm in the practice metronome.
+    Counter,
+}
+
+#[derive(Debug, Clone)]
+pub struct MetronomeClick {
+    pub timestamp: Duration,
+    pub kind: ClickKind,
+}
+
+/// Clicks generated for a song, kept apart from its tracks so they never share a channel.
+#[derive(Debug, Clone, Default)]
+pub struct MetronomeTrack {
+    pub clicks: Vec<MetronomeClick>,
+}
 
-    merged_track
+impl MetronomeTrack {
+    /// Clicks from the first to the last event of `merged_track`, following its tempo changes.
+    pub fn new(
+        merged_track: &MidiTrack,
+        tempo_events: &[TempoEvent],
+        pulses_per_quarter_note: u16,
+        settings: &MetronomeSettings,
+    ) -> Self {
+        let (track_start, track_end) =
+            match (merged_track.events.first(), merged_track.events.last()) {
+                (Some(first), Some(last)) => (first.timestamp, last.timestamp),
+                _ => return Self::default(),
+            };
+
+        let clicks_per_beat = settings.clicks_per_beat.max(1) as u64;
+        let mut clicks = Vec::new();
+
+        for click in 0.. {
+            let pulses = click * pulses_per_quarter_note as u64 / clicks_per_beat;
+            let timestamp = pulses_to_duration(tempo_events, pulses, pulses_per_quarter_note);
+            if timestamp >= track_end {
+                break;
+            }
+            if timestamp < track_start {
+                continue;
+            }
+
+            let beat = (click / clicks_per_beat) as usize;
+            let on_beat = click % clicks_per_beat == 0;
+
+            clicks.push(MetronomeClick {
+                timestamp,
+                kind: settings.click_kind(beat, on_beat),
+            });
+        }
+
+        Self { clicks }
+    }
 }
@@ -1,17 +1,50 @@
-use crate::{utils, MidiTrack, metronome::add_metronome};
+use crate::{
+    gm, utils, MetronomeSettings, MetronomeTrack, MidiTrack, PracticeSettings, TempoEvent,
+};
 use midly::{Format, Smf, Timing};
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Channel that plays notes in a song.
+#[derive(Debug, Clone)]
+pub struct MidiChannel {
+    pub channel: u8,
+    /// First program selected on the channel.
+    pub program: Option<u8>,
+}
+
+impl MidiChannel {
+    pub fn instrument_name(&self) -> &'static str {
+        if self.channel == 9 {
+            "Drums"
+        } else {
+            gm::program_name(self.program.unwrap_or(0))
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Midi {
+    pub path: PathBuf,
     pub format: Format,
     pub tracks: Vec<MidiTrack>,
     pub merged_track: MidiTrack,
+    /// Generated clicks, not part of any track.
+    pub metronome: MetronomeTrack,
+    pub tempo_events: Vec<TempoEvent>,
+    pub pulses_per_quarter_note: u16,
+    /// Exercise this song was generated from, `None` for songs loaded from a file.
+    pub practice: Option<PracticeSettings>,
 }
 
 impl Midi {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, String> {
-        let data = match fs::read(path) {
+        let path = path.as_ref().to_owned();
+
+        let data = match fs::read(&path) {
             Ok(buff) => buff,
             Err(_) => return Err(String::from("Could Not Open File")),
         };
@@ -21,6 +54,11 @@ impl Midi {
             Err(_) => return Err(String::from("Midi Parsing Error (midly lib)")),
         };
 
+        Self::from_smf(path, &smf)
+    }
+
+    /// Song from an already parsed (or generated) file, `path` is only used to name it.
+    pub fn from_smf(path: PathBuf, smf: &Smf) -> Result<Self, String> {
         let u_per_quarter_note: u16 = match smf.header.timing {
             Timing::Metrical(t) => t.as_int(),
             Timing::Timecode(_fps, _u) => {
@@ -52,8 +90,6 @@ impl Midi {
             }
         }
 
-        let mut merged_track = add_metronome(merged_track, &tempo_track, u_per_quarter_note);
-
         merged_track.notes.sort_by_key(|n| n.start);
         merged_track.events.sort_by_key(|n| n.timestamp);
 
@@ -62,10 +98,81 @@ impl Midi {
             note.id = i;
         }
 
+        let metronome = MetronomeTrack::new(
+            &merged_track,
+            &tempo_track,
+            u_per_quarter_note,
+            &MetronomeSettings::default(),
+        );
+
         Ok(Self {
+            path,
             format: smf.header.format,
             tracks,
             merged_track,
+            metronome,
+            tempo_events: tempo_track.to_vec(),
+            pulses_per_quarter_note: u_per_quarter_note,
+            practice: None,
         })
     }
+
+    /// Generate the metronome again with new settings.
+    pub fn set_metronome(&mut self, settings: &MetronomeSettings) {
+        self.metronome = MetronomeTrack::new(
+            &self.merged_track,
+            &self.tempo_events,
+            self.pulses_per_quarter_note,
+            settings,
+        );
+    }
+
+    /// Channels that play notes, sorted by channel number.
+    pub fn channels(&self) -> Vec<MidiChannel> {
+        let mut channels: Vec<MidiChannel> = Vec::new();
+
+        for track in self.tracks.iter() {
+            for note in track.notes.iter() {
+                if !channels.iter().any(|c| c.channel == note.channel) {
+                    channels.push(MidiChannel {
+                        channel: note.channel,
+                        program: None,
+                    });
+                }
+            }
+        }
+
+        for channel in channels.iter_mut() {
+            channel.program = self
+                .tracks
+                .iter()
+                .flat_map(|track| track.events.iter())
+                .filter(|event| event.channel == channel.channel)
+                .filter_map(|event| match event.message {
+                    midly::MidiMessage::ProgramChange { program } => {
+                        Some((event.timestamp, program.as_int()))
+                    }
+                    _ => None,
+                })
+                .min_by_key(|(timestamp, _)| *timestamp)
+                .map(|(_, program)| program);
+        }
+
+        channels.sort_by_key(|c| c.channel);
+        channels
+    }
+
+    /// Song time of a position expressed in pulses, following the tempo map.
+    pub fn pulses_to_duration(&self, pulses: u64) -> Duration {
+        utils::pulses_to_duration(&self.tempo_events, pulses, self.pulses_per_quarter_note)
+    }
+
+    pub fn duration_to_pulses(&self, time: Duration) -> u64 {
+        utils::duration_to_pulses(&self.tempo_events, time, self.pulses_per_quarter_note)
+    }
+
+    /// Tempo (microseconds per quarter note) at a song time.
+    pub fn tempo_at(&self, time: Duration) -> u32 {
+        utils::tempo_at(&self.tempo_events, self.duration_to_pulses(time))
+    }
 }
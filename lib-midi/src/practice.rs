@@ -0,0 +1,321 @@
+use std::{ops::Range, path::PathBuf, time::Duration};
+
+use midly::{
+    num::{u15, u24, u28, u4, u7},
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+};
+
+use crate::{ClickKind, MetronomeClick, MetronomeTrack, Midi};
+
+const PULSES_PER_QUARTER_NOTE: u16 = 480;
+/// Length of the generated notes, short like drum hits.
+const NOTE_LENGTH: u32 = PULSES_PER_QUARTER_NOTE as u32 / 8;
+
+/// Lane of the groups, the snare.
+pub const PRACTICE_PULSE_KEY: u8 = 38;
+/// Lane of the counter rhythm, the bass drum.
+pub const PRACTICE_COUNTER_KEY: u8 = 36;
+
+/// Absolute pulses, whether it is a note on, and the event.
+type PracticeEvent = (u32, bool, TrackEventKind<'static>);
+
+const ACCENT_VELOCITY: u8 = 127;
+const PULSE_VELOCITY: u8 = 80;
+const COUNTER_VELOCITY: u8 = 100;
+
+/// Exercise of the practice metronome, played without a song.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PracticeSettings {
+    /// Pulses per minute on the first bar, a pulse is the unit of `groups`.
+    pub start_bpm: f32,
+    /// Pulses per minute on the last bar, the tempo ramps bar by bar in between.
+    pub end_bpm: f32,
+    pub bars: u32,
+    /// Pulses of a bar split in groups, the first pulse of each group is accented:
+    /// `[4]` is 4/4, `[2, 2, 3]` is 7/8 counted 2+2+3.
+    pub groups: Vec<u32>,
+    /// Notes of the second voice spread evenly over a bar, 3 against `[2]` is 3:2, 0 plays none.
+    pub counter: u32,
+    /// Bars with clicks before `gap_bars` bars without, 1 and 1 mutes every other bar.
+    pub play_bars: u32,
+    pub gap_bars: u32,
+}
+
+impl Default for PracticeSettings {
+    fn default() -> Self {
+        Self {
+            start_bpm: 80.0,
+            end_bpm: 80.0,
+            bars: 16,
+            groups: vec![4],
+            counter: 0,
+            play_bars: 1,
+            gap_bars: 0,
+        }
+    }
+}
+
+impl PracticeSettings {
+    pub fn pulses_per_bar(&self) -> u32 {
+        self.groups.iter().sum::<u32>().max(1)
+    }
+
+    /// One entry per pulse of a bar, set on the first pulse of each group.
+    pub fn accents(&self) -> Vec<bool> {
+        let mut accents = Vec::new();
+        for group in self.groups.iter().filter(|group| **group > 0) {
+            accents.push(true);
+            accents.resize(accents.len() + *group as usize - 1, false);
+        }
+
+        if accents.is_empty() {
+            accents.push(true);
+        }
+        accents
+    }
+
+    fn bpm(&self, bar: u32) -> f32 {
+        let bpm = if self.bars > 1 {
+            let progress = bar as f32 / (self.bars - 1) as f32;
+            self.start_bpm + (self.end_bpm - self.start_bpm) * progress
+        } else {
+            self.start_bpm
+        };
+
+        bpm.max(1.0)
+    }
+
+    fn is_gap(&self, bar: u32) -> bool {
+        self.gap_bars > 0 && bar % (self.play_bars + self.gap_bars) >= self.play_bars
+    }
+
+    /// Song time of the bars left silent in `midi`, their notes are there to be hit, not heard.
+    pub fn gaps(&self, midi: &Midi) -> Vec<Range<Duration>> {
+        let bar_length = self.pulses_per_bar() as u64 * PULSES_PER_QUARTER_NOTE as u64;
+        let bar_start = |bar: u32| midi.pulses_to_duration(bar as u64 * bar_length);
+
+        (0..self.bars)
+            .filter(|bar| self.is_gap(*bar))
+            .map(|bar| bar_start(bar)..bar_start(bar + 1))
+            .collect()
+    }
+
+    /// Notes to play on the lanes of the pulse and the counter rhythm, with the clicks
+    /// in place of the metronome of a song.
+    pub fn to_midi(&self) -> Result<Midi, String> {
+        let ppq = PULSES_PER_QUARTER_NOTE as u32;
+        let bar_length = self.pulses_per_bar() * ppq;
+        let accents = self.accents();
+
+        // Absolute pulses, note offs sort before note ons of the same pulse
+        let mut events: Vec<PracticeEvent> = Vec::new();
+        let mut clicks: Vec<(u32, ClickKind)> = Vec::new();
+
+        for bar in 0..self.bars {
+            let start = bar * bar_length;
+            let tempo = (60_000_000.0 / self.bpm(bar)) as u32;
+            events.push((
+                start,
+                false,
+                TrackEventKind::Meta(MetaMessage::Tempo(u24::new(tempo.min(0xFF_FFFF)))),
+            ));
+
+            let gap = self.is_gap(bar);
+
+            for (pulse, accent) in accents.iter().enumerate() {
+                let pulses = start + pulse as u32 * ppq;
+                let (vel, kind) = if *accent {
+                    (ACCENT_VELOCITY, ClickKind::Accent)
+                } else {
+                    (PULSE_VELOCITY, ClickKind::Beat)
+                };
+
+                push_note(&mut events, pulses, PRACTICE_PULSE_KEY, vel);
+                if !gap {
+                    clicks.push((pulses, kind));
+                }
+            }
+
+            for id in 0..self.counter {
+                let pulses = start + id * bar_length / self.counter;
+
+                push_note(&mut events, pulses, PRACTICE_COUNTER_KEY, COUNTER_VELOCITY);
+                if !gap {
+                    clicks.push((pulses, ClickKind::Counter));
+                }
+            }
+        }
+
+        events.sort_by_key(|(pulses, is_on, _)| (*pulses, *is_on));
+
+        let mut track = Vec::with_capacity(events.len() + 1);
+        let mut last = 0;
+        for (pulses, _, kind) in events {
+            track.push(TrackEvent {
+                delta: u28::new(pulses - last),
+                kind,
+            });
+            last = pulses;
+        }
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        let smf = Smf {
+            header: Header::new(
+                Format::SingleTrack,
+                Timing::Metrical(u15::new(PULSES_PER_QUARTER_NOTE)),
+            ),
+            tracks: vec![track],
+        };
+
+        let mut midi = Midi::from_smf(PathBuf::from("Practice Metronome"), &smf)?;
+
+        clicks.sort_by_key(|(pulses, _)| *pulses);
+        midi.metronome = MetronomeTrack {
+            clicks: clicks
+                .into_iter()
+                .map(|(pulses, kind)| MetronomeClick {
+                    timestamp: midi.pulses_to_duration(pulses as u64),
+                    kind,
+                })
+                .collect(),
+        };
+        midi.practice = Some(self.clone());
+
+        Ok(midi)
+    }
+}
+
+/// Drum hit on `key`, short enough to fit between two pulses.
+fn push_note(events: &mut Vec<PracticeEvent>, pulses: u32, key: u8, vel: u8) {
+    let channel = u4::new(9);
+    let key = u7::new(key);
+
+    events.push((
+        pulses,
+        true,
+        TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::NoteOn {
+                key,
+                vel: u7::new(vel),
+            },
+        },
+    ));
+    events.push((
+        pulses + NOTE_LENGTH,
+        false,
+        TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::NoteOff {
+                key,
+                vel: u7::new(0),
+            },
+        },
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(groups: &[u32]) -> PracticeSettings {
+        PracticeSettings {
+            start_bpm: 60.0,
+            end_bpm: 60.0,
+            bars: 4,
+            groups: groups.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    /// Timestamps of the clicks of `kind`, in milliseconds.
+    fn clicks(midi: &Midi, kind: ClickKind) -> Vec<u128> {
+        midi.metronome
+            .clicks
+            .iter()
+            .filter(|click| click.kind == kind)
+            .map(|click| click.timestamp.as_millis())
+            .collect()
+    }
+
+    #[test]
+    fn accents_mark_the_first_pulse_of_each_group() {
+        assert_eq!(settings(&[4]).accents(), [true, false, false, false]);
+        assert_eq!(
+            settings(&[2, 2, 3]).accents(),
+            [true, false, true, false, true, false, false]
+        );
+    }
+
+    #[test]
+    fn accents_skip_empty_groups() {
+        assert_eq!(settings(&[0, 3]).accents(), [true, false, false]);
+        assert_eq!(settings(&[]).accents(), [true]);
+    }
+
+    #[test]
+    fn gaps_follow_the_played_bars() {
+        let mut practice = settings(&[4]);
+        assert!((0..8).all(|bar| !practice.is_gap(bar)));
+
+        practice.play_bars = 2;
+        practice.gap_bars = 1;
+        let gaps: Vec<u32> = (0..8).filter(|bar| practice.is_gap(*bar)).collect();
+        assert_eq!(gaps, [2, 5]);
+    }
+
+    #[test]
+    fn tempo_ramps_bar_by_bar() {
+        let mut practice = settings(&[4]);
+        practice.end_bpm = 120.0;
+
+        assert_eq!(practice.bpm(0), 60.0);
+        assert_eq!(practice.bpm(1), 80.0);
+        assert_eq!(practice.bpm(3), 120.0);
+
+        practice.bars = 1;
+        assert_eq!(practice.bpm(0), 60.0);
+    }
+
+    #[test]
+    fn ramped_bars_get_shorter() {
+        let mut practice = settings(&[2]);
+        practice.bars = 2;
+        practice.end_bpm = 120.0;
+
+        let midi = practice.to_midi().unwrap();
+        assert_eq!(clicks(&midi, ClickKind::Accent), [0, 2000]);
+        assert_eq!(clicks(&midi, ClickKind::Beat), [1000, 2500]);
+    }
+
+    #[test]
+    fn counter_is_spread_over_the_bar() {
+        let mut practice = settings(&[2]);
+        practice.bars = 1;
+        practice.counter = 3;
+
+        let midi = practice.to_midi().unwrap();
+        assert_eq!(clicks(&midi, ClickKind::Counter), [0, 666, 1333]);
+    }
+
+    #[test]
+    fn gap_bars_keep_their_notes_without_clicks() {
+        let mut practice = settings(&[2]);
+        practice.play_bars = 1;
+        practice.gap_bars = 1;
+
+        let midi = practice.to_midi().unwrap();
+        assert_eq!(clicks(&midi, ClickKind::Accent), [0, 4000]);
+        assert_eq!(midi.merged_track.notes.len(), 8);
+        assert_eq!(
+            practice.gaps(&midi),
+            [
+                Duration::from_secs(2)..Duration::from_secs(4),
+                Duration::from_secs(6)..Duration::from_secs(8),
+            ]
+        );
+    }
+}
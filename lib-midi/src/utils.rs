@@ -56,6 +56,46 @@ pub fn pulses_to_duration(
     res
 }
 
+/// Inverse of `pulses_to_duration`.
+pub fn duration_to_pulses(
+    tempo_events: &[TempoEvent],
+    time: Duration,
+    pulses_per_quarter_note: u16,
+) -> u64 {
+    let time = time.as_micros() as f64;
+    let ppq = pulses_per_quarter_note as f64;
+
+    let mut elapsed = 0.0;
+    let mut last_tempo_event_pulses = 0u64;
+    let mut running_tempo = 500_000;
+
+    for tempo_event in tempo_events.iter() {
+        let delta_pulses = tempo_event.absolute_pulses - last_tempo_event_pulses;
+        let delta_time = delta_pulses as f64 / ppq * running_tempo as f64;
+
+        if elapsed + delta_time > time {
+            break;
+        }
+
+        elapsed += delta_time;
+        running_tempo = tempo_event.tempo;
+        last_tempo_event_pulses = tempo_event.absolute_pulses;
+    }
+
+    let remaining = (time - elapsed) / running_tempo as f64 * ppq;
+    last_tempo_event_pulses + remaining as u64
+}
+
+/// Tempo (microseconds per quarter note) in effect at `pulses`.
+pub fn tempo_at(tempo_events: &[TempoEvent], pulses: u64) -> u32 {
+    tempo_events
+        .iter()
+        .take_while(|event| event.absolute_pulses <= pulses)
+        .last()
+        .map(|event| event.tempo)
+        .unwrap_or(500_000)
+}
+
 pub struct TempoTrack(Vec<TempoEvent>);
 
 impl std::ops::Deref for TempoTrack {
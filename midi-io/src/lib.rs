@@ -1,5 +1,23 @@
 use std::{error::Error, fmt};
 
+#[cfg(target_os = "linux")]
+use midir::os::unix::{VirtualInput, VirtualOutput};
+
+/// Client name of the virtual ports, other applications list them under it.
+#[cfg(target_os = "linux")]
+const VIRTUAL_CLIENT_NAME: &str = "Drumsthesia";
+
+/// Our own virtual ports are hidden from the port lists, connecting to them would loop back.
+#[cfg(target_os = "linux")]
+fn is_virtual_port(name: &str) -> bool {
+    name.starts_with(&format!("{}:", VIRTUAL_CLIENT_NAME))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_virtual_port(_name: &str) -> bool {
+    false
+}
+
 /// An error that can occur during initialization (i.e., while
 /// creating a `MidiInput` or `MidiOutput` object).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -35,12 +53,13 @@ impl MidiOutputManager {
             .ports()
             .iter()
             .filter_map(|p| self.outout.port_name(p).ok())
+            .filter(|name| !is_virtual_port(name))
             .map(MidiOutputPort)
             .collect()
     }
 
     pub fn connect_output(port: MidiOutputPort) -> Option<MidiOutputConnection> {
-        let output = midir::MidiOutput::new("MidiIo-out").unwrap();
+        let output = midir::MidiOutput::new("MidiIo-out").ok()?;
 
         let port = output.ports().into_iter().find(|info| {
             output
@@ -53,6 +72,14 @@ impl MidiOutputManager {
         port.and_then(move |port| output.connect(&port, "MidiIo-in-conn").ok())
             .map(MidiOutputConnection)
     }
+
+    /// Create a virtual output port called `name`, other applications can connect to it
+    /// and receive what is sent through the returned connection.
+    #[cfg(target_os = "linux")]
+    pub fn create_virtual_output(name: &str) -> Option<MidiOutputConnection> {
+        let output = midir::MidiOutput::new(VIRTUAL_CLIENT_NAME).ok()?;
+        output.create_virtual(name).ok().map(MidiOutputConnection)
+    }
 }
 
 pub struct MidiInputManager {
@@ -71,15 +98,18 @@ impl MidiInputManager {
             .ports()
             .iter()
             .filter_map(|p| self.input.port_name(p).ok())
+            .filter(|name| !is_virtual_port(name))
             .map(MidiInputPort)
             .collect()
     }
 
+    /// Connect to `port`, `callback` receives the timestamp of the message
+    /// (in microseconds since an unspecified point in the past) and its raw bytes.
     pub fn connect_input<F>(port: MidiInputPort, mut callback: F) -> Option<MidiInputConnection>
     where
-        F: FnMut(&[u8]) + Send + 'static,
+        F: FnMut(u64, &[u8]) + Send + 'static,
     {
-        let input = midir::MidiInput::new("MidiIo-in").unwrap();
+        let input = midir::MidiInput::new("MidiIo-in").ok()?;
 
         let port = input.ports().into_iter().find(|info| {
             input
@@ -94,9 +124,8 @@ impl MidiInputManager {
                 .connect(
                     &port,
                     "MidiIo-in-conn",
-                    move |_, data, _| {
-                        callback(data);
-                        //
+                    move |stamp, data, _| {
+                        callback(stamp, data);
                     },
                     (),
                 )
@@ -104,6 +133,27 @@ impl MidiInputManager {
         })
         .map(MidiInputConnection)
     }
+
+    /// Create a virtual input port called `name`, other applications can connect to it
+    /// and `callback` receives what they send, like with `connect_input`.
+    #[cfg(target_os = "linux")]
+    pub fn create_virtual_input<F>(name: &str, mut callback: F) -> Option<MidiInputConnection>
+    where
+        F: FnMut(u64, &[u8]) + Send + 'static,
+    {
+        let input = midir::MidiInput::new(VIRTUAL_CLIENT_NAME).ok()?;
+
+        input
+            .create_virtual(
+                name,
+                move |stamp, data, _| {
+                    callback(stamp, data);
+                },
+                (),
+            )
+            .ok()
+            .map(MidiInputConnection)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
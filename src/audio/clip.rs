@@ -0,0 +1,120 @@
+use std::{error::Error, path::Path};
+
+use symphonia::core::{
+    audio::SampleBuffer, codecs::DecoderOptions, errors::Error as DecodeError,
+    formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+
+/// Fully decoded audio file, stored as interleaved stereo samples.
+pub struct Clip {
+    samples: Vec<f32>,
+    sample_rate: u32,
+}
+
+impl Clip {
+    /// Extensions of the files `load` decodes.
+    pub const EXTENSIONS: [&'static str; 4] = ["wav", "ogg", "flac", "mp3"];
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = std::fs::File::open(path)?;
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(ext);
+        }
+
+        let probed = symphonia::default::get_probe().format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?;
+        let mut format = probed.format;
+
+        let track = format.default_track().ok_or("audio file has no tracks")?;
+        let track_id = track.id;
+        let sample_rate = track
+            .codec_params
+            .sample_rate
+            .ok_or("audio file has unknown sample rate")?;
+
+        let mut decoder =
+            symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+        let mut samples = Vec::new();
+
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(DecodeError::IoError(err))
+                    if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    break;
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            if packet.track_id() != track_id {
+                continue;
+            }
+
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(DecodeError::DecodeError(err)) => {
+                    log::warn!("skipping corrupted audio packet: {}", err);
+                    continue;
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            let spec = *decoded.spec();
+            let channels = spec.channels.count();
+
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            buffer.copy_interleaved_ref(decoded);
+
+            for frame in buffer.samples().chunks(channels) {
+                let l = frame[0];
+                let r = frame.get(1).copied().unwrap_or(l);
+                samples.push(l);
+                samples.push(r);
+            }
+        }
+
+        Ok(Self {
+            samples,
+            sample_rate,
+        })
+    }
+
+    /// Length of the clip, in seconds.
+    pub fn duration(&self) -> f64 {
+        (self.samples.len() / 2) as f64 / self.sample_rate as f64
+    }
+
+    /// Linearly interpolated stereo frame at `time` seconds,
+    /// silence outside of the clip.
+    pub fn frame_at(&self, time: f64) -> (f32, f32) {
+        if time < 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let pos = time * self.sample_rate as f64;
+        let id = pos.floor() as usize;
+        let fract = (pos - id as f64) as f32;
+
+        let frame = |id: usize| {
+            let id = id * 2;
+            match (self.samples.get(id), self.samples.get(id + 1)) {
+                (Some(l), Some(r)) => (*l, *r),
+                _ => (0.0, 0.0),
+            }
+        };
+
+        let (l1, r1) = frame(id);
+        let (l2, r2) = frame(id + 1);
+
+        (l1 + (l2 - l1) * fract, r1 + (r2 - r1) * fract)
+    }
+}
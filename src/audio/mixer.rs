@@ -0,0 +1,177 @@
+use std::sync::{mpsc::Sender, Arc, Mutex, Weak};
+
+/// Something that plays through the shared audio output: a synth, a sample kit,
+/// a backing track...
+pub trait AudioSource: Send {
+    /// Write the next `frames.len()` stereo frames.
+    fn render(&mut self, frames: &mut [(f32, f32)]);
+}
+
+/// Sources that play one frame at a time.
+impl<F: FnMut() -> (f32, f32) + Send> AudioSource for F {
+    fn render(&mut self, frames: &mut [(f32, f32)]) {
+        for frame in frames.iter_mut() {
+            *frame = self();
+        }
+    }
+}
+
+/// Frames sent to the session recorder at once.
+const RECORD_BLOCK: usize = 4096;
+
+/// Sums every source playing on the output.
+#[derive(Default)]
+pub struct Mixer {
+    next_id: usize,
+    sources: Vec<(usize, Box<dyn AudioSource>)>,
+    mix: Vec<(f32, f32)>,
+    scratch: Vec<(f32, f32)>,
+    /// Session recorder fed with the mix, and the interleaved samples not sent to it yet.
+    recorder: Option<Sender<Vec<f32>>>,
+    record_block: Vec<f32>,
+}
+
+impl Mixer {
+    /// Mix the next `frames` frames of every source.
+    pub fn render(&mut self, frames: usize) -> &[(f32, f32)] {
+        self.mix.clear();
+        self.mix.resize(frames, (0.0, 0.0));
+        self.scratch.resize(frames, (0.0, 0.0));
+
+        for (_, source) in self.sources.iter_mut() {
+            source.render(&mut self.scratch[..frames]);
+
+            for (mix, (l, r)) in self.mix.iter_mut().zip(self.scratch.iter()) {
+                mix.0 += l;
+                mix.1 += r;
+            }
+        }
+
+        if let Some(recorder) = self.recorder.as_ref() {
+            for (l, r) in self.mix.iter() {
+                self.record_block.push(*l);
+                self.record_block.push(*r);
+            }
+
+            if self.record_block.len() >= RECORD_BLOCK * 2 {
+                let block =
+                    std::mem::replace(&mut self.record_block, Vec::with_capacity(RECORD_BLOCK * 2));
+                // The recorder gave up, keep playing without it
+                if recorder.send(block).is_err() {
+                    self.recorder = None;
+                }
+            }
+        }
+
+        &self.mix
+    }
+
+    /// Start copying the mix to a session recorder, or stop with `None`.
+    pub fn record(&mut self, recorder: Option<Sender<Vec<f32>>>) {
+        if let Some(previous) = self.recorder.take() {
+            previous.send(std::mem::take(&mut self.record_block)).ok();
+        }
+        self.record_block.clear();
+        self.recorder = recorder;
+    }
+}
+
+/// Keeps a source playing, it is removed from the output once dropped.
+pub struct AudioSourceHandle {
+    id: usize,
+    mixer: Weak<Mutex<Mixer>>,
+}
+
+impl Drop for AudioSourceHandle {
+    fn drop(&mut self) {
+        if let Some(mixer) = self.mixer.upgrade() {
+            if let Ok(mut mixer) = mixer.lock() {
+                mixer.sources.retain(|(id, _)| *id != self.id);
+            }
+        }
+    }
+}
+
+pub fn add_source(mixer: &Arc<Mutex<Mixer>>, source: Box<dyn AudioSource>) -> AudioSourceHandle {
+    let id = match mixer.lock() {
+        Ok(mut mixer) => {
+            let id = mixer.next_id;
+            mixer.next_id += 1;
+            mixer.sources.push((id, source));
+            id
+        }
+        Err(_) => usize::MAX,
+    };
+
+    AudioSourceHandle {
+        id,
+        mixer: Arc::downgrade(mixer),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant(value: f32) -> Box<dyn AudioSource> {
+        Box::new(move || (value, -value))
+    }
+
+    #[test]
+    fn sums_sources() {
+        let mixer = Arc::new(Mutex::new(Mixer::default()));
+        let _a = add_source(&mixer, constant(0.25));
+        let _b = add_source(&mixer, constant(0.5));
+
+        let mut mixer = mixer.lock().unwrap();
+        assert_eq!(mixer.render(3), &[(0.75, -0.75); 3]);
+    }
+
+    #[test]
+    fn dropped_handle_removes_source() {
+        let mixer = Arc::new(Mutex::new(Mixer::default()));
+        let a = add_source(&mixer, constant(0.25));
+        let _b = add_source(&mixer, constant(0.5));
+        drop(a);
+
+        let mut mixer = mixer.lock().unwrap();
+        assert_eq!(mixer.render(2), &[(0.5, -0.5); 2]);
+    }
+
+    #[test]
+    fn records_the_mix() {
+        let mixer = Arc::new(Mutex::new(Mixer::default()));
+        let _a = add_source(&mixer, constant(0.25));
+        let _b = add_source(&mixer, constant(0.5));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut mixer = mixer.lock().unwrap();
+        mixer.record(Some(tx));
+
+        mixer.render(RECORD_BLOCK);
+        mixer.render(3);
+        mixer.record(None);
+
+        let blocks: Vec<Vec<f32>> = rx.iter().collect();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].len(), RECORD_BLOCK * 2);
+        assert_eq!(blocks[1], [0.75, -0.75, 0.75, -0.75, 0.75, -0.75]);
+    }
+
+    #[test]
+    fn stops_recording_when_recorder_is_gone() {
+        let mut mixer = Mixer::default();
+        let (tx, rx) = std::sync::mpsc::channel();
+        mixer.record(Some(tx));
+        drop(rx);
+
+        mixer.render(RECORD_BLOCK);
+        assert!(mixer.recorder.is_none());
+    }
+
+    #[test]
+    fn silent_without_sources() {
+        let mut mixer = Mixer::default();
+        assert_eq!(mixer.render(4), &[(0.0, 0.0); 4]);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    error::Error,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+use crate::config::AudioConfig;
+
+mod mixer;
+use mixer::Mixer;
+pub use mixer::{AudioSource, AudioSourceHandle};
+
+mod recorder;
+
+#[cfg(any(feature = "backing_track", feature = "sample_kit"))]
+mod clip;
+#[cfg(any(feature = "backing_track", feature = "sample_kit"))]
+pub use clip::Clip;
+
+/// The one stream of the configured output device, every audio backend plays through it.
+pub struct AudioOutput {
+    stream_config: cpal::StreamConfig,
+    mixer: Arc<Mutex<Mixer>>,
+    _stream: cpal::Stream,
+}
+
+impl AudioOutput {
+    /// Open the configured output, settings that are not available fall back to the defaults.
+    pub fn new(config: &AudioConfig) -> Result<Self, Box<dyn Error>> {
+        let host = host(config.host.as_deref());
+
+        let device = config
+            .device
+            .as_ref()
+            .and_then(|name| {
+                let device = host
+                    .output_devices()
+                    .ok()?
+                    .find(|device| device.name().ok().as_ref() == Some(name));
+                if device.is_none() {
+                    log::warn!("audio device {} not found, using the default one", name);
+                }
+                device
+            })
+            .or_else(|| host.default_output_device())
+            .ok_or("failed to find a default output device")?;
+
+        let default_config = device.default_output_config()?;
+
+        let supported = config.sample_rate.and_then(|rate| {
+            let rate = cpal::SampleRate(rate);
+            let range = device.supported_output_configs().ok()?.find(|range| {
+                range.channels() == default_config.channels()
+                    && range.sample_format() == default_config.sample_format()
+                    && range.min_sample_rate() <= rate
+                    && rate <= range.max_sample_rate()
+            });
+            if range.is_none() {
+                log::warn!(
+                    "sample rate {} not supported, using the default one",
+                    rate.0
+                );
+            }
+            range.map(|range| range.with_sample_rate(rate))
+        });
+        let supported = supported.unwrap_or(default_config);
+
+        let buffer_size = match (config.buffer_size, supported.buffer_size()) {
+            (Some(size), cpal::SupportedBufferSize::Range { min, max }) => {
+                cpal::BufferSize::Fixed(size.clamp(*min, *max))
+            }
+            (Some(size), cpal::SupportedBufferSize::Unknown) => cpal::BufferSize::Fixed(size),
+            (None, _) => cpal::BufferSize::Default,
+        };
+
+        let sample_format = supported.sample_format();
+        let mut stream_config: cpal::StreamConfig = supported.into();
+        stream_config.buffer_size = buffer_size;
+
+        let mixer = Arc::new(Mutex::new(Mixer::default()));
+
+        let _stream = match sample_format {
+            cpal::SampleFormat::F32 => run::<f32>(&device, &stream_config, mixer.clone())?,
+            cpal::SampleFormat::I16 => run::<i16>(&device, &stream_config, mixer.clone())?,
+            cpal::SampleFormat::U16 => run::<u16>(&device, &stream_config, mixer.clone())?,
+        };
+
+        Ok(Self {
+            stream_config,
+            mixer,
+            _stream,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.stream_config.sample_rate.0
+    }
+
+    /// Play `source` until the returned handle is dropped.
+    pub fn add_source(&self, source: impl AudioSource + 'static) -> AudioSourceHandle {
+        mixer::add_source(&self.mixer, Box::new(source))
+    }
+
+    /// Record the mix of every source to a WAV file at `path`, replacing a previous recording.
+    pub fn start_recording(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let recorder = recorder::start(path, self.sample_rate())?;
+        let mut mixer = self.mixer.lock().map_err(|_| "audio output is poisoned")?;
+        mixer.record(Some(recorder));
+        Ok(())
+    }
+
+    /// Finish the recording, the file is complete once the recorder thread writes the last block.
+    pub fn stop_recording(&self) {
+        if let Ok(mut mixer) = self.mixer.lock() {
+            mixer.record(None);
+        }
+    }
+
+    /// Time it takes for a buffer to play, unknown with the default buffer size.
+    pub fn latency(&self) -> Option<Duration> {
+        match self.stream_config.buffer_size {
+            cpal::BufferSize::Fixed(size) => Some(Duration::from_secs_f64(
+                size as f64 / self.stream_config.sample_rate.0 as f64,
+            )),
+            cpal::BufferSize::Default => None,
+        }
+    }
+}
+
+fn run<T: cpal::Sample>(
+    device: &cpal::Device,
+    stream_config: &cpal::StreamConfig,
+    mixer: Arc<Mutex<Mixer>>,
+) -> Result<cpal::Stream, Box<dyn Error>> {
+    let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
+
+    let channels = stream_config.channels as usize;
+
+    let stream = device.build_output_stream(
+        stream_config,
+        move |output: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut mixer = mixer.lock().ok();
+            let mixed = match mixer.as_mut() {
+                Some(mixer) => mixer.render(output.len() / channels),
+                None => &[],
+            };
+
+            for (id, frame) in output.chunks_mut(channels).enumerate() {
+                let (l, r) = mixed.get(id).copied().unwrap_or((0.0, 0.0));
+
+                let l: T = cpal::Sample::from::<f32>(&l);
+                let r: T = cpal::Sample::from::<f32>(&r);
+
+                let channels = [l, r];
+
+                for (id, sample) in frame.iter_mut().enumerate() {
+                    *sample = channels[id % 2];
+                }
+            }
+        },
+        err_fn,
+    )?;
+    stream.play()?;
+
+    Ok(stream)
+}
+
+fn host(name: Option<&str>) -> cpal::Host {
+    name.and_then(|name| {
+        let id = cpal::available_hosts()
+            .into_iter()
+            .find(|id| id.name() == name)?;
+        cpal::host_from_id(id).ok()
+    })
+    .unwrap_or_else(cpal::default_host)
+}
+
+/// Names of the audio hosts (ALSA, JACK, WASAPI, ...) available on this system.
+pub fn hosts() -> Vec<String> {
+    cpal::available_hosts()
+        .into_iter()
+        .map(|id| id.name().to_owned())
+        .collect()
+}
+
+/// Names of the output devices of `host`, the default host when `None`.
+pub fn devices(host_name: Option<&str>) -> Vec<String> {
+    match host(host_name).output_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(err) => {
+            log::error!("{}", err);
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::{error::Error, path::Path, sync::mpsc::Sender};
+
+/// Write interleaved stereo blocks sent to the returned channel to a WAV file at `path`.
+///
+/// The file is written on its own thread, and finalized once every sender is dropped.
+pub fn start(path: &Path, sample_rate: u32) -> Result<Sender<Vec<f32>>, Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)
+        .map_err(|err| format!("failed to create {:?}: {}", path, err))?;
+
+    let (tx, rx) = std::sync::mpsc::channel::<Vec<f32>>();
+    let path = path.to_owned();
+
+    std::thread::Builder::new()
+        .name("session-recorder".into())
+        .spawn(move || {
+            for block in rx {
+                for sample in block {
+                    let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                    if let Err(err) = writer.write_sample(sample) {
+                        log::error!("failed to write {:?}: {}", path, err);
+                        return;
+                    }
+                }
+            }
+
+            match writer.finalize() {
+                Ok(()) => log::info!("session recorded to {:?}", path),
+                Err(err) => log::error!("failed to finalize {:?}: {}", path, err),
+            }
+        })?;
+
+    Ok(tx)
+}
@@ -0,0 +1,157 @@
+mod stretch;
+use stretch::Stretcher;
+
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    audio::{AudioOutput, AudioSource, AudioSourceHandle, Clip},
+    config::BackingTrackConfig,
+};
+
+/// Song time drift (in seconds) tolerated before the audio is snapped back to the chart.
+const MAX_DRIFT: f64 = 0.04;
+
+struct Shared {
+    clip: Option<Clip>,
+    /// Position in the audio file, in seconds.
+    position: f64,
+    /// Audio seconds advanced per output frame.
+    step: f64,
+    /// Seconds per output frame, the step of the audio played at its own speed.
+    frame_time: f64,
+    playing: bool,
+    gain: f32,
+    /// Keeps the pitch when the step differs from `frame_time`.
+    stretcher: Stretcher,
+    stretching: bool,
+}
+
+impl Shared {
+    fn next_frame(&mut self) -> (f32, f32) {
+        if !self.playing {
+            return (0.0, 0.0);
+        }
+
+        let clip = match self.clip.as_ref() {
+            Some(clip) => clip,
+            None => return (0.0, 0.0),
+        };
+
+        let stretching = (self.step - self.frame_time).abs() > self.frame_time * 1e-3;
+        if stretching != self.stretching {
+            self.stretching = stretching;
+            self.stretcher.reset();
+        }
+
+        let (l, r) = if stretching {
+            self.stretcher
+                .next_frame(self.position, |time| clip.frame_at(time))
+        } else {
+            clip.frame_at(self.position)
+        };
+
+        self.position += self.step;
+
+        (l * self.gain, r * self.gain)
+    }
+
+    /// Jump to `position`, without cross-fading from the audio heard before.
+    fn seek(&mut self, position: f64) {
+        self.position = position;
+        self.stretcher.reset();
+    }
+}
+
+/// Plays the shared state on the audio output.
+struct BackingTrackSource(Arc<Mutex<Shared>>);
+
+impl AudioSource for BackingTrackSource {
+    fn render(&mut self, frames: &mut [(f32, f32)]) {
+        let mut shared = self.0.lock().ok();
+
+        for frame in frames.iter_mut() {
+            *frame = match shared.as_mut() {
+                Some(shared) => shared.next_frame(),
+                None => (0.0, 0.0),
+            };
+        }
+    }
+}
+
+/// Audio recording played alongside the MIDI chart.
+///
+/// The chart stays the master clock, the audio position is derived from song time
+/// and corrected whenever it drifts away (pause, seek, speed change).
+pub struct BackingTrack {
+    _source: AudioSourceHandle,
+    shared: Arc<Mutex<Shared>>,
+    sample_rate: f64,
+
+    offset: f64,
+    time_stretch: f64,
+}
+
+impl BackingTrack {
+    pub fn new(
+        config: &BackingTrackConfig,
+        output: &AudioOutput,
+        volume: u8,
+    ) -> Result<Self, Box<dyn Error>> {
+        let sample_rate = output.sample_rate();
+
+        let shared = Arc::new(Mutex::new(Shared {
+            clip: None,
+            position: 0.0,
+            step: 0.0,
+            frame_time: 1.0 / sample_rate as f64,
+            playing: false,
+            gain: volume as f32 / 127.0,
+            stretcher: Stretcher::new(sample_rate),
+            stretching: false,
+        }));
+
+        let _source = output.add_source(BackingTrackSource(shared.clone()));
+
+        {
+            let shared = shared.clone();
+            let path = config.path.clone();
+            std::thread::Builder::new()
+                .name("backing-track-loader".into())
+                .spawn(move || match Clip::load(&path) {
+                    Ok(clip) => {
+                        if let Ok(mut shared) = shared.lock() {
+                            shared.clip = Some(clip);
+                        }
+                    }
+                    Err(err) => log::error!("failed to load backing track {:?}: {}", path, err),
+                })?;
+        }
+
+        Ok(Self {
+            _source,
+            shared,
+            sample_rate: sample_rate as f64,
+
+            offset: config.offset as f64,
+            time_stretch: config.time_stretch as f64,
+        })
+    }
+
+    /// Follow the chart: `time` is the song time (without lead-in) in seconds.
+    pub fn sync(&mut self, time: f32, speed_multiplier: f32, playing: bool) {
+        let expected = self.offset + time as f64 * self.time_stretch;
+
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.step = self.time_stretch * speed_multiplier as f64 / self.sample_rate;
+
+            if !playing || (shared.position - expected).abs() > MAX_DRIFT {
+                shared.seek(expected);
+            }
+
+            shared.playing = playing;
+        }
+    }
+}
@@ -0,0 +1,243 @@
+/// Plays audio faster or slower than recorded without changing its pitch.
+///
+/// Overlapping grains are read at the original speed around the playback position,
+/// each one shifted a little so its waveform lines up with the end of the previous
+/// grain (WSOLA), then cross-faded with a Hann window.
+pub struct Stretcher {
+    /// Seconds between two output frames.
+    frame_time: f64,
+    /// Hann window of a whole grain, two halves sum to one.
+    window: Vec<f32>,
+    /// Frames a grain may move away from the playback position to line up.
+    tolerance: usize,
+    /// Second half of the previous grain, already windowed.
+    tail: Vec<(f32, f32)>,
+    /// Frames ready to be played, and how many of them are already played.
+    hop: Vec<(f32, f32)>,
+    played: usize,
+    /// Where the previous grain would have gone on, `None` after a seek.
+    continuation: Option<f64>,
+    /// Mono scratch buffers of the grain alignment.
+    reference: Vec<f32>,
+    candidates: Vec<f32>,
+}
+
+/// Length of a grain, in seconds.
+const GRAIN: f64 = 0.04;
+/// How far a grain may move to line up with the previous one, in seconds.
+const TOLERANCE: f64 = 0.01;
+/// Only every n-th frame is compared while lining grains up.
+const STRIDE: usize = 2;
+
+impl Stretcher {
+    pub fn new(sample_rate: u32) -> Self {
+        let half = ((GRAIN * sample_rate as f64) as usize / 2).max(1);
+        let grain = half * 2;
+
+        let window = (0..grain)
+            .map(|id| {
+                let phase = id as f64 / grain as f64;
+                (0.5 - 0.5 * (std::f64::consts::TAU * phase).cos()) as f32
+            })
+            .collect();
+
+        let tolerance = (TOLERANCE * sample_rate as f64) as usize;
+
+        Self {
+            frame_time: 1.0 / sample_rate as f64,
+            window,
+            tolerance,
+            tail: vec![(0.0, 0.0); half],
+            hop: Vec::with_capacity(half),
+            played: 0,
+            continuation: None,
+            reference: Vec::with_capacity(half),
+            candidates: Vec::with_capacity(tolerance * 2 + half),
+        }
+    }
+
+    /// Forget the previous grain, the next one fades in at the new position.
+    pub fn reset(&mut self) {
+        self.tail.iter_mut().for_each(|frame| *frame = (0.0, 0.0));
+        self.hop.clear();
+        self.played = 0;
+        self.continuation = None;
+    }
+
+    /// Next output frame, `position` is the time of the audio that should be heard now,
+    /// `read` gives the frame of the audio at a time, in seconds.
+    pub fn next_frame(&mut self, position: f64, read: impl Fn(f64) -> (f32, f32)) -> (f32, f32) {
+        if self.played >= self.hop.len() {
+            self.next_hop(position, &read);
+        }
+
+        let frame = self.hop[self.played];
+        self.played += 1;
+        frame
+    }
+
+    fn next_hop(&mut self, position: f64, read: &impl Fn(f64) -> (f32, f32)) {
+        let half = self.tail.len();
+
+        let start = match self.continuation {
+            Some(continuation) => self.aligned_start(position, continuation, read),
+            None => position,
+        };
+
+        self.hop.clear();
+        for id in 0..half {
+            let (l, r) = read(start + id as f64 * self.frame_time);
+            let w = self.window[id];
+            let (tail_l, tail_r) = self.tail[id];
+            self.hop.push((tail_l + l * w, tail_r + r * w));
+        }
+
+        for id in 0..half {
+            let (l, r) = read(start + (half + id) as f64 * self.frame_time);
+            let w = self.window[half + id];
+            self.tail[id] = (l * w, r * w);
+        }
+
+        self.played = 0;
+        self.continuation = Some(start + half as f64 * self.frame_time);
+    }
+
+    /// Start of the grain closest to `position` that best continues the previous grain.
+    fn aligned_start(
+        &mut self,
+        position: f64,
+        continuation: f64,
+        read: &impl Fn(f64) -> (f32, f32),
+    ) -> f64 {
+        let half = self.tail.len();
+        let mono = |time: f64| {
+            let (l, r) = read(time);
+            l + r
+        };
+
+        self.reference.clear();
+        self.reference.extend(
+            (0..half)
+                .step_by(STRIDE)
+                .map(|id| mono(continuation + id as f64 * self.frame_time)),
+        );
+
+        let first = position - self.tolerance as f64 * self.frame_time;
+        self.candidates.clear();
+        self.candidates.extend(
+            (0..self.tolerance * 2 + half).map(|id| mono(first + id as f64 * self.frame_time)),
+        );
+
+        let mut best = (self.tolerance, f32::MIN);
+        for offset in (0..=self.tolerance * 2).step_by(STRIDE) {
+            let (mut correlation, mut energy) = (0.0, 0.0);
+            for (id, reference) in self.reference.iter().enumerate() {
+                let candidate = self.candidates[offset + id * STRIDE];
+                correlation += reference * candidate;
+                energy += candidate * candidate;
+            }
+
+            let score = correlation / (energy + 1e-6).sqrt();
+            if score > best.1 {
+                best = (offset, score);
+            }
+        }
+
+        first + best.0 as f64 * self.frame_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    fn sine(frequency: f64) -> impl Fn(f64) -> (f32, f32) {
+        move |time: f64| {
+            let value = (std::f64::consts::TAU * frequency * time).sin() as f32;
+            (value, value)
+        }
+    }
+
+    /// Play `read` at `rate` for `seconds`, like the backing track does.
+    fn render(rate: f64, seconds: f64, read: impl Fn(f64) -> (f32, f32)) -> Vec<f32> {
+        let mut stretcher = Stretcher::new(SAMPLE_RATE);
+        let step = rate / SAMPLE_RATE as f64;
+
+        let mut position = 0.0;
+        let mut out = Vec::new();
+        for _ in 0..(seconds * SAMPLE_RATE as f64) as usize {
+            out.push(stretcher.next_frame(position, &read).0);
+            position += step;
+        }
+        out
+    }
+
+    /// Frequency of `samples`, from the zero crossings past the first grain.
+    fn frequency(samples: &[f32]) -> f64 {
+        let samples = &samples[SAMPLE_RATE as usize / 10..];
+        let crossings = samples
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count();
+
+        crossings as f64 * SAMPLE_RATE as f64 / samples.len() as f64
+    }
+
+    #[test]
+    fn window_halves_sum_to_one() {
+        let stretcher = Stretcher::new(SAMPLE_RATE);
+        let half = stretcher.tail.len();
+
+        for id in 0..half {
+            let sum = stretcher.window[id] + stretcher.window[half + id];
+            assert!((sum - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn keeps_pitch_when_slowed_down() {
+        let out = render(0.5, 1.0, sine(440.0));
+        assert!((frequency(&out) - 440.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn keeps_pitch_when_sped_up() {
+        let out = render(1.5, 1.0, sine(440.0));
+        assert!((frequency(&out) - 440.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn keeps_level_of_steady_tone() {
+        let out = render(0.75, 1.0, sine(440.0));
+        let out = &out[SAMPLE_RATE as usize / 10..];
+        let rms = (out.iter().map(|sample| sample * sample).sum::<f32>() / out.len() as f32).sqrt();
+
+        // Grains out of phase would cancel each other
+        assert!((rms - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.05);
+    }
+
+    #[test]
+    fn follows_position() {
+        // Audio is a ramp, each frame tells the time it was read at
+        let out = render(0.5, 1.0, |time: f64| (time as f32, time as f32));
+
+        // Half a second of audio in one second, within a grain of the expected time
+        let last = *out.last().unwrap();
+        assert!((last - 0.5).abs() < GRAIN as f32);
+    }
+
+    #[test]
+    fn fades_in_after_reset() {
+        let mut stretcher = Stretcher::new(SAMPLE_RATE);
+        let read = |_: f64| (1.0, 1.0);
+
+        for _ in 0..SAMPLE_RATE / 10 {
+            stretcher.next_frame(0.0, read);
+        }
+        stretcher.reset();
+
+        assert_eq!(stretcher.next_frame(0.0, read), (0.0, 0.0));
+    }
+}
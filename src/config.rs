@@ -1,8 +1,16 @@
-use std::{path::PathBuf, fmt::Display};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
 
-use crate::output_manager::OutputDescriptor;
+use crate::{
+    input_manager::VelocityCurve,
+    output_manager::{OutputDescriptor, OutputGroup},
+};
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum PlayingSceneLayout {
@@ -39,6 +47,346 @@ pub struct ColorSchema {
     pub beige: (u8, u8, u8),
 }
 
+/// Audio recording attached to a MIDI file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BackingTrackConfig {
+    pub path: PathBuf,
+
+    /// Position in the audio file, in seconds, that lines up with the start of the MIDI file.
+    #[serde(default)]
+    pub offset: f32,
+
+    /// Playback rate of the audio relative to the chart, the pitch is kept,
+    /// used to absorb small tempo differences between the recording and the MIDI file.
+    #[serde(default = "default_time_stretch")]
+    pub time_stretch: f32,
+}
+
+impl BackingTrackConfig {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            offset: 0.0,
+            time_stretch: default_time_stretch(),
+        }
+    }
+}
+
+/// Effects of the built-in synth, the default is the dry sound of older versions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SynthEffectsConfig {
+    #[serde(default = "default_master_gain")]
+    pub master_gain: f32,
+    /// Pulls peaks down instead of letting loud passages clip.
+    #[serde(default)]
+    pub limiter: bool,
+
+    /// From 0.0 to 1.0.
+    #[serde(default = "default_reverb_room_size")]
+    pub reverb_room_size: f32,
+    /// From 0.0 to 1.0.
+    #[serde(default)]
+    pub reverb_damping: f32,
+    /// From 0.0 to 1.0.
+    #[serde(default = "default_reverb_level")]
+    pub reverb_level: f32,
+    /// Reverb send of the drum channel (user and guide drums), replaces the one of the song.
+    #[serde(default)]
+    pub drums_reverb: u8,
+    /// Reverb send of every other channel, replaces the one of the song.
+    #[serde(default)]
+    pub music_reverb: u8,
+    /// Chorus send of the music channels, replaces the one of the song.
+    #[serde(default)]
+    pub chorus: u8,
+}
+
+impl Default for SynthEffectsConfig {
+    fn default() -> Self {
+        Self {
+            master_gain: default_master_gain(),
+            limiter: false,
+            reverb_room_size: default_reverb_room_size(),
+            reverb_damping: 0.0,
+            reverb_level: default_reverb_level(),
+            drums_reverb: 0,
+            music_reverb: 0,
+            chorus: 0,
+        }
+    }
+}
+
+/// Note value clicked by the metronome.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Subdivision {
+    Quarter,
+    Eighth,
+    Triplet,
+    Sixteenth,
+}
+
+impl Subdivision {
+    pub const ALL: [Subdivision; 4] = [
+        Subdivision::Quarter,
+        Subdivision::Eighth,
+        Subdivision::Triplet,
+        Subdivision::Sixteenth,
+    ];
+
+    pub fn clicks_per_beat(self) -> u32 {
+        match self {
+            Subdivision::Quarter => 1,
+            Subdivision::Eighth => 2,
+            Subdivision::Triplet => 3,
+            Subdivision::Sixteenth => 4,
+        }
+    }
+}
+
+impl Display for Subdivision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Subdivision::Quarter => write!(f, "Quarter"),
+            Subdivision::Eighth => write!(f, "Eighth"),
+            Subdivision::Triplet => write!(f, "Triplet"),
+            Subdivision::Sixteenth => write!(f, "Sixteenth"),
+        }
+    }
+}
+
+/// Metronome generated for every song, the default is the woodblock of older versions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MetronomeConfig {
+    #[serde(default = "default_subdivision")]
+    pub subdivision: Subdivision,
+    /// One entry per beat of a bar, accented beats play `accent_key` louder.
+    #[serde(default = "default_accents")]
+    pub accents: Vec<bool>,
+    /// General MIDI program of the click.
+    #[serde(default = "default_metronome_program")]
+    pub program: u8,
+    #[serde(default = "default_accent_key")]
+    pub accent_key: u8,
+    #[serde(default = "default_regular_key")]
+    pub regular_key: u8,
+    /// Second voice of the practice metronome polyrhythms.
+    #[serde(default = "default_counter_key")]
+    pub counter_key: u8,
+    /// Sample played instead of the program, velocity still tells accents apart.
+    #[serde(default)]
+    pub sample: Option<PathBuf>,
+    /// Bars counted in before the song and after each resume.
+    #[serde(default)]
+    pub count_in_bars: u8,
+    /// Channel of the clicks on MIDI outputs (0 based), channel 16 by default,
+    /// `None` keeps them off MIDI outputs so no channel of the song is taken over.
+    #[serde(default = "default_metronome_midi_channel")]
+    pub midi_channel: Option<u8>,
+}
+
+impl Default for MetronomeConfig {
+    fn default() -> Self {
+        Self {
+            subdivision: default_subdivision(),
+            accents: default_accents(),
+            program: default_metronome_program(),
+            accent_key: default_accent_key(),
+            regular_key: default_regular_key(),
+            counter_key: default_counter_key(),
+            sample: None,
+            count_in_bars: 0,
+            midi_channel: default_metronome_midi_channel(),
+        }
+    }
+}
+
+impl MetronomeConfig {
+    pub fn settings(&self) -> lib_midi::MetronomeSettings {
+        lib_midi::MetronomeSettings {
+            clicks_per_beat: self.subdivision.clicks_per_beat(),
+            accents: self.accents.clone(),
+            program: self.program,
+            accent_key: self.accent_key,
+            regular_key: self.regular_key,
+            counter_key: self.counter_key,
+        }
+    }
+}
+
+/// Exercise played by the practice metronome, without a song.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PracticeConfig {
+    #[serde(default = "default_practice_bpm")]
+    pub start_bpm: f32,
+    /// Tempo of the last bar, different from `start_bpm` to ramp up or down.
+    #[serde(default = "default_practice_bpm")]
+    pub end_bpm: f32,
+    #[serde(default = "default_practice_bars")]
+    pub bars: u32,
+    /// Pulses of a bar split in accented groups, `[2, 2, 3]` counts 7/8 as 2+2+3.
+    #[serde(default = "default_practice_groups")]
+    pub groups: Vec<u32>,
+    /// Clicks of the second sound per bar, 0 plays no polyrhythm.
+    #[serde(default)]
+    pub counter: u32,
+    /// Bars clicked before `gap_bars` silent bars, 0 gap bars always clicks.
+    #[serde(default = "default_practice_play_bars")]
+    pub play_bars: u32,
+    #[serde(default)]
+    pub gap_bars: u32,
+}
+
+impl Default for PracticeConfig {
+    fn default() -> Self {
+        Self {
+            start_bpm: default_practice_bpm(),
+            end_bpm: default_practice_bpm(),
+            bars: default_practice_bars(),
+            groups: default_practice_groups(),
+            counter: 0,
+            play_bars: default_practice_play_bars(),
+            gap_bars: 0,
+        }
+    }
+}
+
+impl PracticeConfig {
+    pub fn settings(&self) -> lib_midi::PracticeSettings {
+        lib_midi::PracticeSettings {
+            start_bpm: self.start_bpm,
+            end_bpm: self.end_bpm,
+            bars: self.bars,
+            groups: self.groups.clone(),
+            counter: self.counter,
+            play_bars: self.play_bars,
+            gap_bars: self.gap_bars,
+        }
+    }
+}
+
+/// Mixer settings of a song channel.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChannelMix {
+    /// Scales the drums or music volume, 127 leaves it unchanged.
+    #[serde(default = "default_channel_volume")]
+    pub volume: u8,
+    /// From -64 (left) to 63 (right).
+    #[serde(default)]
+    pub pan: i8,
+    #[serde(default)]
+    pub mute: bool,
+    #[serde(default)]
+    pub solo: bool,
+}
+
+impl Default for ChannelMix {
+    fn default() -> Self {
+        Self {
+            volume: default_channel_volume(),
+            pan: 0,
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+/// Per channel mix of a MIDI file, channels without an entry play as written in the file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MixerConfig {
+    #[serde(default)]
+    pub channels: HashMap<u8, ChannelMix>,
+}
+
+impl MixerConfig {
+    pub fn channel(&self, channel: u8) -> Option<&ChannelMix> {
+        self.channels.get(&channel)
+    }
+
+    pub fn channel_mut(&mut self, channel: u8) -> &mut ChannelMix {
+        self.channels.entry(channel).or_default()
+    }
+
+    /// Gain applied to the volume of `channel`, from 0.0 to 1.0.
+    pub fn gain(&self, channel: u8) -> f32 {
+        let any_solo = self.channels.values().any(|mix| mix.solo);
+
+        match self.channel(channel) {
+            Some(mix) if mix.mute || (any_solo && !mix.solo) => 0.0,
+            Some(mix) => mix.volume as f32 / 127.0,
+            None if any_solo => 0.0,
+            None => 1.0,
+        }
+    }
+
+    /// Pan controller value of `channel`, `None` keeps the pan of the file.
+    pub fn pan(&self, channel: u8) -> Option<u8> {
+        self.channel(channel)
+            .map(|mix| (mix.pan as i16 + 64).clamp(0, 127) as u8)
+    }
+}
+
+/// Drops phantom hits sent by e-kits before they reach the game,
+/// everything is disabled by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct InputFilterConfig {
+    /// Hits quieter than this are ignored.
+    #[serde(default)]
+    pub min_velocity: u8,
+
+    /// Per lane overrides of `min_velocity`, keyed by lane id (eg. 9 for the snare drum).
+    #[serde(default)]
+    pub lane_min_velocity: HashMap<u8, u8>,
+
+    /// A second hit on the same lane within this many milliseconds is ignored.
+    #[serde(default)]
+    pub retrigger_mask_ms: u32,
+
+    /// A hit within this many milliseconds of a louder hit on another lane is ignored.
+    #[serde(default)]
+    pub crosstalk_window_ms: u32,
+}
+
+impl InputFilterConfig {
+    pub fn min_velocity(&self, lane: u8) -> u8 {
+        self.lane_min_velocity
+            .get(&lane)
+            .copied()
+            .unwrap_or(self.min_velocity)
+    }
+}
+
+/// Audio output used by the built-in synth, sample kits and backing tracks,
+/// `None` fields use the system defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AudioConfig {
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub device: Option<String>,
+    /// In frames, smaller buffers lower the latency but can crackle.
+    #[serde(default)]
+    pub buffer_size: Option<u32>,
+    #[serde(default)]
+    pub sample_rate: Option<u32>,
+}
+
+/// Drum note played by a computer keyboard key.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub note: u8,
+    #[serde(default = "default_key_velocity")]
+    pub velocity: u8,
+}
+
+impl KeyBinding {
+    pub fn new(note: u8) -> Self {
+        Self {
+            note,
+            velocity: default_key_velocity(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "default_speed_multiplier")]
@@ -65,6 +413,23 @@ pub struct Config {
     #[serde(default = "default_metronome_volume")]
     pub metronome_volume: u8,
 
+    #[serde(default)]
+    pub metronome: MetronomeConfig,
+
+    #[serde(default)]
+    pub practice: PracticeConfig,
+
+    #[serde(default = "default_backing_track_volume")]
+    pub backing_track_volume: u8,
+
+    /// Backing tracks keyed by the path of the MIDI file they belong to.
+    #[serde(default)]
+    pub backing_tracks: HashMap<PathBuf, BackingTrackConfig>,
+
+    /// Channel mixes keyed by the path of the MIDI file they belong to.
+    #[serde(default)]
+    pub mixers: HashMap<PathBuf, MixerConfig>,
+
     #[serde(default = "default_layout")]
     pub layout: PlayingSceneLayout,
 
@@ -78,9 +443,50 @@ pub struct Config {
 
     #[serde(default = "default_output")]
     pub output: Option<String>,
-    pub input: Option<String>,
+    /// Groups sent somewhere else than `output`.
+    #[serde(default)]
+    pub output_routes: HashMap<OutputGroup, String>,
+    /// Name of the note map applied to drum notes sent to the output.
+    #[serde(default)]
+    pub note_map: Option<String>,
+    /// Names of every input port to connect.
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// Input whose MIDI clock and transport drive the playback, `None` follows the internal clock.
+    #[serde(default)]
+    pub clock_input: Option<String>,
+    /// Output that receives MIDI clock and transport of the playback, `None` sends no clock.
+    #[serde(default)]
+    pub clock_output: Option<String>,
+    /// Publish "Drumsthesia In" and "Drumsthesia Out" virtual MIDI ports (Linux only).
+    #[serde(default)]
+    pub virtual_ports: bool,
+    /// Single input used by older versions, migrated into `inputs` on load.
+    #[serde(default, skip_serializing)]
+    input: Option<String>,
+    #[serde(default)]
+    pub input_filter: InputFilterConfig,
+    /// Computer keyboard keys that play drums, holding shift plays them at full velocity.
+    #[serde(default = "default_keyboard_drums")]
+    pub keyboard_drums: HashMap<VirtualKeyCode, KeyBinding>,
+    /// Applied to user hits before they reach the output.
+    #[serde(default)]
+    pub velocity_curve: VelocityCurve,
+    /// Per lane overrides of `velocity_curve`, keyed by lane id.
+    #[serde(default)]
+    pub lane_velocity_curves: HashMap<u8, VelocityCurve>,
 
     pub soundfont_path: Option<PathBuf>,
+    /// Manifest of the kit played by the sample kit output.
+    #[serde(default)]
+    pub sample_kit_path: Option<PathBuf>,
+    #[serde(default)]
+    pub audio: AudioConfig,
+    #[serde(default)]
+    pub synth_effects: SynthEffectsConfig,
+    /// Record what the audio output plays to the sessions folder.
+    #[serde(default)]
+    pub record_sessions: bool,
 }
 
 impl Default for Config {
@@ -107,7 +513,7 @@ impl Config {
             None
         };
 
-        config.unwrap_or_else(|| Self {
+        let mut config = config.unwrap_or_else(|| Self {
             speed_multiplier: default_speed_multiplier(),
             playback_offset: default_playback_offset(),
             wait_for_notes: default_wait_for_notes(),
@@ -116,13 +522,45 @@ impl Config {
             drums_volume: default_drums_volume(),
             music_volume: default_music_volume(),
             metronome_volume: default_metronome_volume(),
+            metronome: MetronomeConfig::default(),
+            practice: PracticeConfig::default(),
+            backing_track_volume: default_backing_track_volume(),
+            backing_tracks: HashMap::new(),
+            mixers: HashMap::new(),
             layout: default_layout(),
             color_schema: default_color_schema(),
             background_color: Default::default(),
             output: default_output(),
+            output_routes: HashMap::new(),
+            note_map: None,
+            inputs: Vec::new(),
+            clock_input: None,
+            clock_output: None,
+            virtual_ports: false,
             input: None,
+            input_filter: InputFilterConfig::default(),
+            keyboard_drums: default_keyboard_drums(),
+            velocity_curve: VelocityCurve::default(),
+            lane_velocity_curves: HashMap::new(),
             soundfont_path: None,
-        })
+            sample_kit_path: None,
+            audio: AudioConfig::default(),
+            synth_effects: SynthEffectsConfig::default(),
+            record_sessions: false,
+        });
+
+        if let Some(input) = config.input.take() {
+            if !config.inputs.contains(&input) {
+                config.inputs.push(input);
+            }
+        }
+
+        config.velocity_curve.sort_points();
+        for curve in config.lane_velocity_curves.values_mut() {
+            curve.sort_points();
+        }
+
+        config
     }
 
     pub fn set_output(&mut self, v: &OutputDescriptor) {
@@ -133,8 +571,51 @@ impl Config {
         }
     }
 
-    pub fn set_input<D: std::fmt::Display>(&mut self, v: Option<D>) {
-        self.input = v.map(|v| v.to_string());
+    pub fn set_output_route(&mut self, group: OutputGroup, v: Option<&OutputDescriptor>) {
+        if let Some(v) = v {
+            self.output_routes.insert(group, v.to_string());
+        } else {
+            self.output_routes.remove(&group);
+        }
+    }
+
+    pub fn set_input_enabled<D: std::fmt::Display>(&mut self, v: &D, enabled: bool) {
+        let name = v.to_string();
+        self.inputs.retain(|input| input != &name);
+
+        if enabled {
+            self.inputs.push(name);
+        }
+    }
+
+    pub fn velocity_curve(&self, lane: u8) -> &VelocityCurve {
+        self.lane_velocity_curves
+            .get(&lane)
+            .unwrap_or(&self.velocity_curve)
+    }
+
+    pub fn backing_track(&self, midi: &Path) -> Option<&BackingTrackConfig> {
+        self.backing_tracks.get(midi)
+    }
+
+    pub fn mixer(&self, midi: &Path) -> Option<&MixerConfig> {
+        self.mixers.get(midi)
+    }
+
+    pub fn set_mixer(&mut self, midi: &Path, v: MixerConfig) {
+        if v.channels.is_empty() {
+            self.mixers.remove(midi);
+        } else {
+            self.mixers.insert(midi.to_owned(), v);
+        }
+    }
+
+    pub fn set_backing_track(&mut self, midi: &Path, v: Option<BackingTrackConfig>) {
+        if let Some(v) = v {
+            self.backing_tracks.insert(midi.to_owned(), v);
+        } else {
+            self.backing_tracks.remove(midi);
+        }
     }
 }
 
@@ -181,6 +662,98 @@ fn default_metronome_volume() -> u8 {
     127
 }
 
+fn default_backing_track_volume() -> u8 {
+    100
+}
+
+fn default_subdivision() -> Subdivision {
+    Subdivision::Eighth
+}
+
+fn default_accents() -> Vec<bool> {
+    vec![true, false, false, false]
+}
+
+fn default_metronome_program() -> u8 {
+    // Woodblock
+    115
+}
+
+fn default_accent_key() -> u8 {
+    64
+}
+
+fn default_regular_key() -> u8 {
+    62
+}
+
+fn default_counter_key() -> u8 {
+    69
+}
+
+fn default_metronome_midi_channel() -> Option<u8> {
+    Some(15)
+}
+
+fn default_practice_bpm() -> f32 {
+    80.0
+}
+
+fn default_practice_bars() -> u32 {
+    16
+}
+
+fn default_practice_groups() -> Vec<u32> {
+    vec![4]
+}
+
+fn default_practice_play_bars() -> u32 {
+    1
+}
+
+fn default_master_gain() -> f32 {
+    0.3
+}
+
+fn default_reverb_room_size() -> f32 {
+    0.2
+}
+
+fn default_reverb_level() -> f32 {
+    0.9
+}
+
+fn default_channel_volume() -> u8 {
+    127
+}
+
+fn default_time_stretch() -> f32 {
+    1.0
+}
+
+fn default_key_velocity() -> u8 {
+    100
+}
+
+fn default_keyboard_drums() -> HashMap<VirtualKeyCode, KeyBinding> {
+    [
+        (VirtualKeyCode::A, 49),
+        (VirtualKeyCode::S, 38),
+        (VirtualKeyCode::D, 48),
+        (VirtualKeyCode::G, 47),
+        (VirtualKeyCode::H, 41),
+        (VirtualKeyCode::J, 42),
+        (VirtualKeyCode::K, 46),
+        (VirtualKeyCode::L, 51),
+        (VirtualKeyCode::Semicolon, 57),
+        (VirtualKeyCode::B, 36),
+        (VirtualKeyCode::N, 44),
+    ]
+    .into_iter()
+    .map(|(key, note)| (key, KeyBinding::new(note)))
+    .collect()
+}
+
 fn default_layout() -> PlayingSceneLayout {
     PlayingSceneLayout::Horizontal
 }
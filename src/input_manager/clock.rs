@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use midly::{
+    live::{LiveEvent, SystemCommon, SystemRealtime},
+    num::u14,
+};
+
+/// MIDI clock and transport messages exchanged with a DAW or a hardware sequencer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockMessage {
+    /// Sent 24 times per quarter note.
+    Tick,
+    Start,
+    Continue,
+    Stop,
+    /// Position in MIDI beats (16th notes) since the start of the song.
+    SongPosition(u16),
+}
+
+impl ClockMessage {
+    pub fn from_live_event(event: &LiveEvent) -> Option<Self> {
+        match event {
+            LiveEvent::Realtime(SystemRealtime::TimingClock) => Some(ClockMessage::Tick),
+            LiveEvent::Realtime(SystemRealtime::Start) => Some(ClockMessage::Start),
+            LiveEvent::Realtime(SystemRealtime::Continue) => Some(ClockMessage::Continue),
+            LiveEvent::Realtime(SystemRealtime::Stop) => Some(ClockMessage::Stop),
+            LiveEvent::Common(SystemCommon::SongPosition(pos)) => {
+                Some(ClockMessage::SongPosition(pos.as_int()))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn to_live_event(self) -> LiveEvent<'static> {
+        match self {
+            ClockMessage::Tick => LiveEvent::Realtime(SystemRealtime::TimingClock),
+            ClockMessage::Start => LiveEvent::Realtime(SystemRealtime::Start),
+            ClockMessage::Continue => LiveEvent::Realtime(SystemRealtime::Continue),
+            ClockMessage::Stop => LiveEvent::Realtime(SystemRealtime::Stop),
+            ClockMessage::SongPosition(beats) => {
+                LiveEvent::Common(SystemCommon::SongPosition(u14::from_int_lossy(beats)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ClockEvent {
+    pub message: ClockMessage,
+    /// Same time base as `MidiEvent::timestamp` of input events.
+    pub timestamp: Duration,
+}
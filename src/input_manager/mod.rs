@@ -1,60 +1,234 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 use lib_midi::MidiEvent;
 use midly::{live::LiveEvent, MidiMessage};
 
 use crate::{EventLoopProxy, NeothesiaEvent};
 
+mod clock;
+pub use clock::{ClockEvent, ClockMessage};
+
+mod velocity_curve;
+pub use velocity_curve::VelocityCurve;
+
+/// Input latency above which a midir timestamp is considered unreliable.
+const MAX_INPUT_LATENCY: Duration = Duration::from_millis(50);
+
+/// What a connected port is used for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputRole {
+    /// Channel messages are played as drum input.
+    pub drums: bool,
+    /// MIDI clock and transport messages drive the playback.
+    pub clock: bool,
+}
+
 pub struct InputManager {
-    input: midi_io::MidiInputManager,
+    /// `None` when MIDI input could not be initialized.
+    input: Option<midi_io::MidiInputManager>,
     tx: EventLoopProxy,
-    current_connection: Option<midi_io::MidiInputConnection>,
+    /// Every connected port feeds the same event stream.
+    connections: HashMap<midi_io::MidiInputPort, (InputRole, midi_io::MidiInputConnection)>,
+    /// "Drumsthesia In" virtual port, played as drum input.
+    #[cfg(target_os = "linux")]
+    virtual_input: Option<midi_io::MidiInputConnection>,
+    epoch: Instant,
 }
 
 impl InputManager {
     pub fn new(tx: EventLoopProxy) -> Self {
-        let input = midi_io::MidiInputManager::new().unwrap();
+        let input = match midi_io::MidiInputManager::new() {
+            Ok(input) => Some(input),
+            Err(err) => {
+                log::error!("{}", err);
+                None
+            }
+        };
+
         Self {
             input,
             tx,
-            current_connection: None,
+            connections: HashMap::new(),
+            #[cfg(target_os = "linux")]
+            virtual_input: None,
+            epoch: Instant::now(),
         }
     }
 
+    pub fn is_available(&self) -> bool {
+        self.input.is_some()
+    }
+
     pub fn inputs(&self) -> Vec<midi_io::MidiInputPort> {
-        self.input.inputs()
+        self.input
+            .as_ref()
+            .map(|input| input.inputs())
+            .unwrap_or_default()
+    }
+
+    /// Moment at which an input event was received,
+    /// input events are timestamped relative to the manager creation.
+    pub fn instant(&self, event: &MidiEvent) -> Instant {
+        self.epoch + event.timestamp
+    }
+
+    pub fn clock_instant(&self, event: &ClockEvent) -> Instant {
+        self.epoch + event.timestamp
+    }
+
+    /// Event timestamped like the ones received from MIDI inputs,
+    /// used for hits played with the computer keyboard and mouse.
+    pub fn local_event(&self, channel: u8, message: MidiMessage) -> MidiEvent {
+        MidiEvent {
+            channel,
+            message,
+            delta: 0,
+            timestamp: self.epoch.elapsed(),
+            track_id: 0,
+        }
+    }
+
+    /// Keep exactly `ports` and the `clock` port connected, already open connections are reused.
+    pub fn connect_inputs(
+        &mut self,
+        ports: &[midi_io::MidiInputPort],
+        clock: Option<&midi_io::MidiInputPort>,
+    ) {
+        let mut roles: HashMap<midi_io::MidiInputPort, InputRole> = HashMap::new();
+        for port in ports {
+            roles.entry(port.clone()).or_default().drums = true;
+        }
+        if let Some(port) = clock {
+            roles.entry(port.clone()).or_default().clock = true;
+        }
+
+        self.connections
+            .retain(|port, (role, _)| roles.get(port) == Some(role));
+
+        for (port, role) in roles {
+            self.connect_input(port, role);
+        }
+    }
+
+    pub fn connect_input(&mut self, port: midi_io::MidiInputPort, role: InputRole) {
+        if self.connections.contains_key(&port) {
+            return;
+        }
+
+        let connection =
+            midi_io::MidiInputManager::connect_input(port.clone(), self.input_callback(role));
+
+        match connection {
+            Some(connection) => {
+                self.connections.insert(port, (role, connection));
+            }
+            None => log::error!("failed to connect to midi input {}", port),
+        }
+    }
+
+    /// Drop the connection to a port that went away, so it can be connected again.
+    pub fn disconnect_input(&mut self, port: &midi_io::MidiInputPort) {
+        self.connections.remove(port);
+    }
+
+    /// Publish or remove the "Drumsthesia In" virtual port.
+    #[cfg(target_os = "linux")]
+    pub fn set_virtual_input(&mut self, enabled: bool) {
+        if !enabled {
+            self.virtual_input = None;
+        } else if self.virtual_input.is_none() {
+            let role = InputRole {
+                drums: true,
+                clock: false,
+            };
+            self.virtual_input = midi_io::MidiInputManager::create_virtual_input(
+                "Drumsthesia In",
+                self.input_callback(role),
+            );
+            if self.virtual_input.is_none() {
+                log::error!("failed to create virtual midi input");
+            }
+        }
     }
 
-    pub fn connect_input(&mut self, port: midi_io::MidiInputPort) {
+    fn input_callback(&self, role: InputRole) -> impl FnMut(u64, &[u8]) + Send + 'static {
         let tx = self.tx.clone();
-        self.current_connection = midi_io::MidiInputManager::connect_input(port, move |message| {
-            let event = LiveEvent::parse(message).unwrap();
-            match &event {
-                LiveEvent::Midi { channel: _, message } => match message {
-                    MidiMessage::NoteOn { key: _, vel: _ } => {
-                        let event = MidiEvent {
-                            channel: 9,
-                            message: *message,
-                            delta: 0,
-                            timestamp: Duration::ZERO,
-                            track_id: 0,
-                        };
-                        tx.proxy.send_event(NeothesiaEvent::MidiInput(event)).unwrap();
-                    }
-                    MidiMessage::NoteOff { key: _, vel: _ } => {
-                        let event = MidiEvent {
-                            channel: 9,
-                            message: *message,
-                            delta: 0,
-                            timestamp: Duration::ZERO,
-                            track_id: 0,
-                        };
-                        tx.proxy.send_event(NeothesiaEvent::MidiInput(event)).unwrap();
+        let epoch = self.epoch;
+        // Each port has its own timestamp origin
+        let mut input_clock = InputClock::default();
+
+        move |stamp, message| {
+            let event = match LiveEvent::parse(message) {
+                Ok(event) => event,
+                Err(err) => {
+                    log::warn!("skipping midi input {:?}: {}", message, err);
+                    return;
+                }
+            };
+
+            let timestamp = input_clock.instant(stamp).saturating_duration_since(epoch);
+
+            if let Some(message) = ClockMessage::from_live_event(&event) {
+                if role.clock {
+                    let event = ClockEvent { message, timestamp };
+                    tx.send_event(NeothesiaEvent::MidiClock(event));
+                }
+                return;
+            }
+
+            if !role.drums {
+                return;
+            }
+
+            if let LiveEvent::Midi { channel, message } = event {
+                let message = match message {
+                    MidiMessage::NoteOn { key, vel } if vel == 0 => {
+                        MidiMessage::NoteOff { key, vel }
                     }
-                    _ => {}
-                },
-                _ => {}
+                    message => message,
+                };
+
+                let event = MidiEvent {
+                    channel: channel.as_int(),
+                    message,
+                    delta: 0,
+                    timestamp,
+                    track_id: 0,
+                };
+                tx.send_event(NeothesiaEvent::MidiInput(event));
+            }
+        }
+    }
+}
+
+/// Maps midir timestamps onto `Instant`s.
+#[derive(Default)]
+struct InputClock {
+    anchor: Option<Instant>,
+}
+
+impl InputClock {
+    fn instant(&mut self, stamp: u64) -> Instant {
+        let now = Instant::now();
+        let stamp = Duration::from_micros(stamp);
+        let candidate = now.checked_sub(stamp);
+
+        // The callback only ever runs late, so the earliest anchor is the most accurate one
+        match (self.anchor, candidate) {
+            (Some(anchor), Some(candidate)) if candidate >= anchor => {}
+            _ => self.anchor = candidate,
+        }
+
+        match self.anchor.map(|anchor| anchor + stamp) {
+            Some(instant) if now.saturating_duration_since(instant) < MAX_INPUT_LATENCY => instant,
+            _ => {
+                // Clocks drifted apart or the backend does not provide timestamps
+                self.anchor = candidate;
+                now
             }
-        });
+        }
     }
 }
@@ -0,0 +1,148 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Reshapes the velocity of user hits before they are sent to the output.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum VelocityCurve {
+    #[default]
+    Linear,
+    /// Boosts soft hits, for stiff pads that never reach full velocity.
+    Log,
+    /// Tames soft hits, for sensitive pads.
+    Exp,
+    /// Every hit is sent with the same velocity.
+    Fixed(u8),
+    /// `(input, output)` points, linearly interpolated.
+    Custom(Vec<(u8, u8)>),
+}
+
+impl VelocityCurve {
+    /// Curves selectable from the settings screen, custom points come from `settings.ron`.
+    pub const PRESETS: [VelocityCurve; 4] = [
+        VelocityCurve::Linear,
+        VelocityCurve::Log,
+        VelocityCurve::Exp,
+        VelocityCurve::Fixed(100),
+    ];
+
+    pub fn apply(&self, vel: u8) -> u8 {
+        let x = vel.min(127) as f32 / 127.0;
+
+        let y = match self {
+            VelocityCurve::Linear => x,
+            VelocityCurve::Log => (1.0 + 9.0 * x).log10(),
+            VelocityCurve::Exp => (10f32.powf(x) - 1.0) / 9.0,
+            VelocityCurve::Fixed(vel) => return (*vel).clamp(1, 127),
+            VelocityCurve::Custom(points) => return Self::interpolate(points, vel),
+        };
+
+        ((y * 127.0).round() as u8).clamp(1, 127)
+    }
+
+    /// Custom points are interpolated in input order, they are sorted once when the settings load.
+    pub fn sort_points(&mut self) {
+        if let VelocityCurve::Custom(points) = self {
+            points.sort_by_key(|(input, _)| *input);
+        }
+    }
+
+    /// `points` have to be sorted by input.
+    fn interpolate(points: &[(u8, u8)], vel: u8) -> u8 {
+        let (first, last) = match (points.first(), points.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return vel,
+        };
+
+        let out = if vel <= first.0 {
+            first.1
+        } else if vel >= last.0 {
+            last.1
+        } else {
+            points
+                .windows(2)
+                .find(|w| vel >= w[0].0 && vel <= w[1].0)
+                .map(|w| {
+                    let (x0, y0) = (w[0].0 as f32, w[0].1 as f32);
+                    let (x1, y1) = (w[1].0 as f32, w[1].1 as f32);
+                    let t = if x1 > x0 {
+                        (vel as f32 - x0) / (x1 - x0)
+                    } else {
+                        0.0
+                    };
+                    (y0 + (y1 - y0) * t).round() as u8
+                })
+                .unwrap_or(vel)
+        };
+
+        out.clamp(1, 127)
+    }
+}
+
+impl fmt::Display for VelocityCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VelocityCurve::Linear => write!(f, "Linear"),
+            VelocityCurve::Log => write!(f, "Log"),
+            VelocityCurve::Exp => write!(f, "Exp"),
+            VelocityCurve::Fixed(vel) => write!(f, "Fixed ({})", vel),
+            VelocityCurve::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(points: &[(u8, u8)]) -> VelocityCurve {
+        let mut curve = VelocityCurve::Custom(points.to_vec());
+        curve.sort_points();
+        curve
+    }
+
+    #[test]
+    fn custom_hits_its_points() {
+        let curve = custom(&[(0, 20), (64, 90), (127, 127)]);
+        assert_eq!(curve.apply(0), 20);
+        assert_eq!(curve.apply(64), 90);
+        assert_eq!(curve.apply(127), 127);
+    }
+
+    #[test]
+    fn custom_interpolates_between_points() {
+        let curve = custom(&[(0, 20), (64, 90), (127, 127)]);
+        assert_eq!(curve.apply(32), 55);
+        assert_eq!(curve.apply(96), 109);
+    }
+
+    #[test]
+    fn custom_holds_past_its_points() {
+        let curve = custom(&[(20, 40), (100, 120)]);
+        assert_eq!(curve.apply(5), 40);
+        assert_eq!(curve.apply(127), 120);
+    }
+
+    #[test]
+    fn custom_points_are_sorted() {
+        let curve = custom(&[(127, 127), (0, 1), (64, 90)]);
+        assert_eq!(
+            curve,
+            VelocityCurve::Custom(vec![(0, 1), (64, 90), (127, 127)])
+        );
+        assert_eq!(curve.apply(32), 46);
+    }
+
+    #[test]
+    fn custom_output_stays_in_range() {
+        assert_eq!(custom(&[(0, 0), (127, 0)]).apply(64), 1);
+        assert_eq!(custom(&[(0, 200)]).apply(64), 127);
+        assert_eq!(custom(&[]).apply(64), 64);
+    }
+
+    #[test]
+    fn fixed_ignores_velocity() {
+        assert_eq!(VelocityCurve::Fixed(110).apply(3), 110);
+        assert_eq!(VelocityCurve::Fixed(0).apply(3), 1);
+    }
+}
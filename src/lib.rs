@@ -14,6 +14,14 @@ pub use output_manager::OutputManager;
 
 pub mod input_manager;
 
+pub mod port_watcher;
+
+#[cfg(feature = "audio")]
+pub mod audio;
+
+#[cfg(feature = "backing_track")]
+pub mod backing_track;
+
 pub mod config;
 
 pub mod target;
@@ -44,6 +52,8 @@ pub enum NeothesiaEvent {
     #[cfg(feature = "app")]
     MainMenu(crate::scene::menu_scene::Event),
     MidiInput(MidiEvent),
+    MidiClock(input_manager::ClockEvent),
+    Port(port_watcher::PortEvent),
     GoBack,
 }
 
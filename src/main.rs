@@ -113,6 +113,13 @@ impl Drumsthesia {
                 }
             },
             NeothesiaEvent::MidiInput(event) => self.midi_event(event),
+            NeothesiaEvent::MidiClock(event) => {
+                self.game_scene.clock_event(&mut self.target, event);
+            }
+            NeothesiaEvent::Port(event) => {
+                self.target.port_event(event);
+                self.game_scene.port_event(&mut self.target, event);
+            }
         }
     }
 
@@ -9,6 +9,7 @@ use midly::{MidiMessage, live::LiveEvent, num::u7};
 pub struct MidiOutputConnection {
     conn: midi_io::MidiOutputConnection,
     active_notes: HashSet<ActiveNote>,
+    used_channels: HashSet<u8>,
 }
 
 impl From<midi_io::MidiOutputConnection> for MidiOutputConnection {
@@ -16,10 +17,23 @@ impl From<midi_io::MidiOutputConnection> for MidiOutputConnection {
         Self {
             conn,
             active_notes: Default::default(),
+            used_channels: Default::default(),
         }
     }
 }
 
+impl MidiOutputConnection {
+    fn send(&mut self, channel: u8, message: MidiMessage) {
+        let event = LiveEvent::Midi {
+            channel: channel.into(),
+            message,
+        };
+        let mut data = Vec::new();
+        event.write(&mut data).unwrap();
+        self.conn.send(&data).ok();
+    }
+}
+
 pub struct MidiBackend {
     manager: midi_io::MidiOutputManager,
 }
@@ -43,57 +57,64 @@ impl MidiBackend {
         midi_io::MidiOutputManager::connect_output(port.port.clone())
             .map(MidiOutputConnection::from)
     }
+
+    #[cfg(target_os = "linux")]
+    pub fn new_virtual_output_connection(name: &str) -> Option<MidiOutputConnection> {
+        midi_io::MidiOutputManager::create_virtual_output(name).map(MidiOutputConnection::from)
+    }
 }
 
 impl OutputConnection for MidiOutputConnection {
     fn midi_event(&mut self, channel: u8, message: MidiMessage) {
-        match &message {
-            MidiMessage::ProgramChange { program: _ } => {
-                let event = LiveEvent::Midi {
-                    channel: channel.into(),
-                    message
-                };
-                let mut data = Vec::new();
-                event.write(&mut data).unwrap();
-                self.conn.send(&data).ok();
-            },
-            MidiMessage::NoteOn { key, vel: _ } => {
-                let event = LiveEvent::Midi {
-                    channel: channel.into(),
-                    message
-                };
-                let mut data = Vec::new();
-                event.write(&mut data).unwrap();
-                self.conn.send(&data).ok();
-                self.active_notes.insert(ActiveNote { key: *key, channel });
-            },
-            MidiMessage::NoteOff { key, vel: _ } => {
-                let event = LiveEvent::Midi {
-                    channel: channel.into(),
-                    message
-                };
-                let mut data = Vec::new();
-                event.write(&mut data).unwrap();
-                self.conn.send(&data).ok();
-                self.active_notes.remove(&ActiveNote { key: *key, channel });
+        match message {
+            MidiMessage::NoteOn { key, vel } if vel > 0 => {
+                self.active_notes.insert(ActiveNote { key, channel });
+            }
+            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                self.active_notes.remove(&ActiveNote { key, channel });
             }
             _ => {}
         }
+
+        self.used_channels.insert(channel);
+        self.send(channel, message);
+    }
+
+    fn stop_all(&mut self) {
+        for note in std::mem::take(&mut self.active_notes) {
+            self.send(
+                note.channel,
+                MidiMessage::NoteOff {
+                    key: note.key,
+                    vel: u7::new(0),
+                },
+            );
+        }
+
+        for channel in std::mem::take(&mut self.used_channels) {
+            // All Notes Off
+            self.send(
+                channel,
+                MidiMessage::Controller {
+                    controller: u7::new(123),
+                    value: u7::new(0),
+                },
+            );
+            // Reset All Controllers
+            self.send(
+                channel,
+                MidiMessage::Controller {
+                    controller: u7::new(121),
+                    value: u7::new(0),
+                },
+            );
+        }
     }
 }
 
 impl Drop for MidiOutputConnection {
     fn drop(&mut self) {
-        for note in self.active_notes.iter() {
-                let key = u7::try_from(note.key.into()).unwrap();
-                let event = LiveEvent::Midi {
-                    channel: note.channel.into(),
-                    message: MidiMessage::NoteOff { key, vel: u7::new(0) }
-                };
-                let mut data = Vec::new();
-                event.write(&mut data).unwrap();
-                self.conn.send(&data).ok();
-        }
+        self.stop_all();
     }
 }
 
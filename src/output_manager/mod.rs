@@ -1,24 +1,50 @@
 mod midi_backend;
+mod note_map;
+#[cfg(target_os = "linux")]
+use midi_backend::MidiOutputConnection;
+pub use note_map::NoteMap;
+
 use lib_midi::midly;
 use midi_backend::{MidiBackend, MidiPortInfo};
 
 #[cfg(feature = "synth")]
 mod synth_backend;
 
+#[cfg(feature = "sample_kit")]
+mod sample_kit_backend;
+#[cfg(feature = "sample_kit")]
+use sample_kit_backend::{is_sfz, SampleKitBackend, SampleKitOutputConnection};
+
 use midir::MidiInputPort;
 use midly::MidiMessage;
 #[cfg(feature = "synth")]
 use synth_backend::SynthBackend;
 
+#[cfg(feature = "audio")]
+use crate::audio::AudioOutput;
+use crate::config::AudioConfig;
+#[cfg(feature = "synth")]
+use crate::config::SynthEffectsConfig;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "audio")]
+use std::rc::Rc;
 use std::{
+    collections::HashMap,
     fmt::{self, Display, Formatter},
     path::PathBuf,
 };
 
+/// The metronome sample has a sampler of its own, any channel will do.
+#[cfg(feature = "sample_kit")]
+const METRONOME_SAMPLE_CHANNEL: u8 = 0;
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum OutputDescriptor {
     #[cfg(feature = "synth")]
     Synth(Option<PathBuf>),
+    /// Path of the kit manifest.
+    #[cfg(feature = "sample_kit")]
+    SampleKit(Option<PathBuf>),
     MidiOut(MidiPortInfo),
     DummyOutput,
 }
@@ -28,12 +54,46 @@ impl Display for OutputDescriptor {
         match self {
             #[cfg(feature = "synth")]
             OutputDescriptor::Synth(_) => write!(f, "Buildin Synth"),
+            #[cfg(feature = "sample_kit")]
+            OutputDescriptor::SampleKit(_) => write!(f, "Sample Kit"),
             OutputDescriptor::MidiOut(info) => write!(f, "{}", info),
             OutputDescriptor::DummyOutput => write!(f, "No Output"),
         }
     }
 }
 
+/// Kind of traffic that can be routed to its own output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OutputGroup {
+    /// Hits played by the user on the kit.
+    UserDrums,
+    /// Drum notes of the MIDI file.
+    GuideDrums,
+    /// Every other channel of the MIDI file.
+    Music,
+    Metronome,
+}
+
+impl OutputGroup {
+    pub const ALL: [OutputGroup; 4] = [
+        OutputGroup::UserDrums,
+        OutputGroup::GuideDrums,
+        OutputGroup::Music,
+        OutputGroup::Metronome,
+    ];
+}
+
+impl Display for OutputGroup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputGroup::UserDrums => write!(f, "Drums"),
+            OutputGroup::GuideDrums => write!(f, "Guide"),
+            OutputGroup::Music => write!(f, "Music"),
+            OutputGroup::Metronome => write!(f, "Metronome"),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct InputDescriptior {
     pub input: MidiInputPort,
@@ -47,17 +107,44 @@ impl fmt::Debug for InputDescriptior {
 
 pub trait OutputConnection {
     fn midi_event(&mut self, _channel: u8, _msg: MidiMessage){}
+    /// Click of the metronome, outputs with room outside the MIDI channels play it there,
+    /// the others on `midi_channel` and not at all without one.
+    fn metronome_event(&mut self, midi_channel: Option<u8>, msg: MidiMessage) {
+        if let Some(channel) = midi_channel {
+            self.midi_event(channel, msg);
+        }
+    }
+    /// Silence everything that is still sounding, called when the song is stopped.
+    fn stop_all(&mut self) {}
 }
 
 struct DummyOutput {}
 impl OutputConnection for DummyOutput {}
 
 pub struct OutputManager {
+    /// Output device shared by the synth, the sample kits and the backing track.
+    #[cfg(feature = "audio")]
+    audio_output: Option<Rc<AudioOutput>>,
     #[cfg(feature = "synth")]
     synth_backend: Option<SynthBackend>,
+    #[cfg(feature = "synth")]
+    synth_effects: SynthEffectsConfig,
+    #[cfg(feature = "sample_kit")]
+    sample_kit_backend: Option<SampleKitBackend>,
+    /// Sample played by the metronome instead of its output.
+    #[cfg(feature = "sample_kit")]
+    metronome_sample: Option<(PathBuf, SampleKitOutputConnection)>,
+    /// Channel of the clicks on MIDI outputs, `None` keeps them off.
+    metronome_midi_channel: Option<u8>,
     midi_backend: Option<MidiBackend>,
 
-    output_connection: (OutputDescriptor, Box<dyn OutputConnection>),
+    output: OutputDescriptor,
+    routes: HashMap<OutputGroup, OutputDescriptor>,
+    connections: Vec<(OutputDescriptor, Box<dyn OutputConnection>)>,
+    note_map: Option<NoteMap>,
+    /// "Drumsthesia Out" virtual port, mirrors guide drums and music.
+    #[cfg(target_os = "linux")]
+    virtual_output: Option<MidiOutputConnection>,
 
     pub selected_output_id: Option<usize>,
     pub selected_font_path: Option<PathBuf>,
@@ -65,20 +152,35 @@ pub struct OutputManager {
 
 impl Default for OutputManager {
     fn default() -> Self {
-        Self::new()
+        Self::new(&AudioConfig::default())
+    }
+}
+
+#[cfg(feature = "audio")]
+fn audio_output(audio: &AudioConfig) -> Option<Rc<AudioOutput>> {
+    match AudioOutput::new(audio) {
+        Ok(output) => Some(Rc::new(output)),
+        Err(err) => {
+            log::error!("{:?}", err);
+            None
+        }
     }
 }
 
 impl OutputManager {
-    pub fn new() -> Self {
+    pub fn new(_audio: &AudioConfig) -> Self {
+        #[cfg(feature = "audio")]
+        let audio_output = audio_output(_audio);
+
         #[cfg(feature = "synth")]
-        let synth_backend = match SynthBackend::new() {
-            Ok(synth_backend) => Some(synth_backend),
-            Err(err) => {
-                log::error!("{:?}", err);
-                None
-            }
-        };
+        let synth_effects = SynthEffectsConfig::default();
+        #[cfg(feature = "synth")]
+        let synth_backend = audio_output
+            .clone()
+            .map(|output| SynthBackend::new(output, &synth_effects));
+
+        #[cfg(feature = "sample_kit")]
+        let sample_kit_backend = audio_output.clone().map(SampleKitBackend::new);
 
         let midi_backend = match MidiBackend::new() {
             Ok(midi_device_manager) => Some(midi_device_manager),
@@ -89,18 +191,126 @@ impl OutputManager {
         };
 
         Self {
+            #[cfg(feature = "audio")]
+            audio_output,
             #[cfg(feature = "synth")]
             synth_backend,
+            #[cfg(feature = "synth")]
+            synth_effects,
+            #[cfg(feature = "sample_kit")]
+            sample_kit_backend,
+            #[cfg(feature = "sample_kit")]
+            metronome_sample: None,
+            metronome_midi_channel: None,
             midi_backend,
 
-            output_connection: (OutputDescriptor::DummyOutput, Box::new(DummyOutput {})),
+            output: OutputDescriptor::DummyOutput,
+            routes: HashMap::new(),
+            connections: vec![(OutputDescriptor::DummyOutput, Box::new(DummyOutput {}))],
+            note_map: None,
+            #[cfg(target_os = "linux")]
+            virtual_output: None,
             selected_output_id: None,
             selected_font_path: None,
         }
     }
 
+    pub fn is_midi_available(&self) -> bool {
+        self.midi_backend.is_some()
+    }
+
+    #[cfg(feature = "synth")]
+    pub fn is_synth_available(&self) -> bool {
+        self.synth_backend.is_some()
+    }
+
+    /// Shared audio output, `None` when no device could be opened.
+    #[cfg(feature = "audio")]
+    pub fn audio_output(&self) -> Option<Rc<AudioOutput>> {
+        self.audio_output.clone()
+    }
+
+    /// Latency of the audio output, unknown with the default buffer size.
+    #[cfg(feature = "audio")]
+    pub fn audio_latency(&self) -> Option<std::time::Duration> {
+        self.audio_output
+            .as_ref()
+            .and_then(|output| output.latency())
+    }
+
+    /// Reverb, chorus, gain and limiter of the built-in synth, applied while it plays.
+    #[cfg(feature = "synth")]
+    pub fn set_synth_effects(&mut self, effects: &SynthEffectsConfig) {
+        self.synth_effects = effects.clone();
+        if let Some(synth) = self.synth_backend.as_mut() {
+            synth.set_effects(effects);
+        }
+    }
+
+    /// Record everything the audio output plays to a WAV file at `path`.
+    #[cfg(feature = "audio")]
+    pub fn start_recording(&mut self, path: &std::path::Path) {
+        let result = match self.audio_output.as_ref() {
+            Some(output) => output.start_recording(path),
+            None => Err("audio output unavailable".into()),
+        };
+
+        if let Err(err) = result {
+            log::error!("failed to record the session: {}", err);
+        }
+    }
+
+    #[cfg(feature = "audio")]
+    pub fn stop_recording(&mut self) {
+        if let Some(output) = self.audio_output.as_ref() {
+            output.stop_recording();
+        }
+    }
+
+    /// Reopen the audio outputs with new device settings.
+    pub fn set_audio_config(&mut self, _audio: &AudioConfig) {
+        #[cfg(feature = "audio")]
+        {
+            self.audio_output = audio_output(_audio);
+        }
+        #[cfg(feature = "synth")]
+        {
+            self.synth_backend = self
+                .audio_output
+                .clone()
+                .map(|output| SynthBackend::new(output, &self.synth_effects));
+        }
+        #[cfg(feature = "sample_kit")]
+        {
+            self.sample_kit_backend = self.audio_output.clone().map(SampleKitBackend::new);
+
+            let sample = self.metronome_sample.take().map(|(path, _)| path);
+            self.set_metronome_sample(sample.as_deref());
+        }
+
+        let is_audio = |desc: &OutputDescriptor| match desc {
+            #[cfg(feature = "synth")]
+            OutputDescriptor::Synth(_) => true,
+            #[cfg(feature = "sample_kit")]
+            OutputDescriptor::SampleKit(_) => true,
+            _ => false,
+        };
+
+        let reopen: Vec<OutputDescriptor> = self
+            .connections
+            .iter()
+            .map(|(desc, _)| desc.clone())
+            .filter(is_audio)
+            .collect();
+
+        self.connections.retain(|(desc, _)| !is_audio(desc));
+        for desc in reopen {
+            self.open(&desc);
+        }
+    }
+
     pub fn current_output(&self) -> &OutputDescriptor {
-        &self.output_connection.0
+        &self.output
     }
 
     pub fn outputs(&self) -> Vec<OutputDescriptor> {
@@ -110,6 +320,10 @@ impl OutputManager {
         if let Some(synth) = &self.synth_backend {
             outs.append(&mut synth.get_outputs());
         }
+        #[cfg(feature = "sample_kit")]
+        if let Some(sample_kit) = &self.sample_kit_backend {
+            outs.append(&mut sample_kit.get_outputs());
+        }
         if let Some(midi) = &self.midi_backend {
             outs.append(&mut midi.get_outputs());
         }
@@ -119,38 +333,273 @@ impl OutputManager {
         outs
     }
 
+    /// Connect the default output, used by every group without a route of its own.
     pub fn connect(&mut self, desc: OutputDescriptor) {
-        if desc != self.output_connection.0 {
-            match desc {
-                #[cfg(feature = "synth")]
-                OutputDescriptor::Synth(ref font) => {
-                    if let Some(ref mut synth) = self.synth_backend {
-                        if let Some(font) = font.clone() {
-                            self.output_connection =
-                                (desc, Box::new(synth.new_output_connection(&font)));
-                            self.selected_font_path = Some(font);
-                        } else if let Some(path) = crate::utils::resources::default_sf2() {
-                            if path.exists() {
-                                self.output_connection =
-                                    (desc, Box::new(synth.new_output_connection(&path)));
-                                self.selected_font_path = Some(path);
+        if desc != self.output && self.open(&desc) {
+            self.output = desc;
+            self.close_unused();
+        }
+    }
+
+    /// Route a group to its own output, `None` sends it to the default output again.
+    pub fn connect_route(&mut self, group: OutputGroup, desc: Option<OutputDescriptor>) {
+        match desc {
+            Some(desc) => {
+                if self.routes.get(&group) != Some(&desc) && self.open(&desc) {
+                    self.routes.insert(group, desc);
+                }
+            }
+            None => {
+                self.routes.remove(&group);
+            }
+        }
+
+        self.close_unused();
+    }
+
+    /// MIDI output port called `name`, if it is currently available.
+    pub fn midi_port(&self, name: &str) -> Option<OutputDescriptor> {
+        self.midi_backend.as_ref().and_then(|midi| {
+            midi.get_outputs()
+                .into_iter()
+                .find(|desc| desc.to_string() == name)
+        })
+    }
+
+    /// Reopen the output and routes using the MIDI port `name`, once the device is plugged back in.
+    pub fn reconnect_midi_port(&mut self, name: &str) {
+        let fresh = if let Some(fresh) = self.midi_port(name) {
+            fresh
+        } else {
+            return;
+        };
+
+        let is_port = |desc: &OutputDescriptor| {
+            matches!(desc, OutputDescriptor::MidiOut(_)) && desc.to_string() == name
+        };
+
+        if !is_port(&self.output) && !self.routes.values().any(is_port) {
+            return;
+        }
+
+        self.connections.retain(|(desc, _)| !is_port(desc));
+
+        if is_port(&self.output) {
+            self.output = fresh.clone();
+        }
+        for desc in self.routes.values_mut() {
+            if is_port(desc) {
+                *desc = fresh.clone();
+            }
+        }
+
+        self.open(&fresh);
+    }
+
+    fn open(&mut self, desc: &OutputDescriptor) -> bool {
+        if self.connections.iter().any(|(d, _)| d == desc) {
+            return true;
+        }
+
+        let conn: Option<Box<dyn OutputConnection>> = match desc {
+            #[cfg(feature = "synth")]
+            OutputDescriptor::Synth(font) => {
+                let font = font
+                    .clone()
+                    .or_else(|| crate::utils::resources::default_sf2().filter(|path| path.exists()));
+
+                match font {
+                    // SFZ instruments are played by the sampler of the sample kit output
+                    #[cfg(feature = "sample_kit")]
+                    Some(font) if is_sfz(&font) => {
+                        let conn = self
+                            .sample_kit_backend
+                            .as_mut()
+                            .map(|sample_kit| sample_kit.new_output_connection(&font));
+
+                        match conn {
+                            Some(Ok(conn)) => {
+                                self.selected_font_path = Some(font);
+                                Some(Box::new(conn))
                             }
+                            Some(Err(err)) => {
+                                log::error!("{}", err);
+                                None
+                            }
+                            None => None,
                         }
                     }
+                    Some(font) => {
+                        let conn = self
+                            .synth_backend
+                            .as_mut()
+                            .map(|synth| synth.new_output_connection(&font));
+
+                        match conn {
+                            Some(Ok(conn)) => {
+                                self.selected_font_path = Some(font);
+                                Some(Box::new(conn))
+                            }
+                            Some(Err(err)) => {
+                                log::error!("{}", err);
+                                None
+                            }
+                            None => None,
+                        }
+                    }
+                    None => None,
                 }
-                OutputDescriptor::MidiOut(ref info) => {
-                    if let Some(conn) = MidiBackend::new_output_connection(info) {
-                        self.output_connection = (desc, Box::new(conn));
+            }
+            #[cfg(feature = "sample_kit")]
+            OutputDescriptor::SampleKit(kit) => match (self.sample_kit_backend.as_mut(), kit) {
+                (Some(sample_kit), Some(kit)) => match sample_kit.new_output_connection(kit) {
+                    Ok(conn) => Some(Box::new(conn)),
+                    Err(err) => {
+                        log::error!("{}", err);
+                        None
                     }
+                },
+                (Some(_), None) => {
+                    log::error!("no sample kit selected");
+                    None
                 }
-                OutputDescriptor::DummyOutput => {
-                    self.output_connection = (desc, Box::new(DummyOutput {}));
+                _ => None,
+            },
+            OutputDescriptor::MidiOut(info) => MidiBackend::new_output_connection(info)
+                .map(|conn| Box::new(conn) as Box<dyn OutputConnection>),
+            OutputDescriptor::DummyOutput => Some(Box::new(DummyOutput {})),
+        };
+
+        if let Some(conn) = conn {
+            self.connections.push((desc.clone(), conn));
+            true
+        } else {
+            log::error!("failed to connect to {}", desc);
+            false
+        }
+    }
+
+    fn close_unused(&mut self) {
+        let output = &self.output;
+        let routes = &self.routes;
+        self.connections
+            .retain(|(desc, _)| desc == output || routes.values().any(|d| d == desc));
+    }
+
+    /// Publish or remove the "Drumsthesia Out" virtual port.
+    #[cfg(target_os = "linux")]
+    pub fn set_virtual_output(&mut self, enabled: bool) {
+        if !enabled {
+            self.virtual_output = None;
+        } else if self.virtual_output.is_none() {
+            self.virtual_output = MidiBackend::new_virtual_output_connection("Drumsthesia Out");
+            if self.virtual_output.is_none() {
+                log::error!("failed to create virtual midi output");
+            }
+        }
+    }
+
+    /// Play the metronome with the sample at `path` instead of its output, `None` goes back to it.
+    pub fn set_metronome_sample(&mut self, _path: Option<&std::path::Path>) {
+        #[cfg(feature = "sample_kit")]
+        {
+            let path = if let Some(path) = _path {
+                path
+            } else {
+                self.metronome_sample = None;
+                return;
+            };
+
+            if matches!(&self.metronome_sample, Some((open, _)) if open == path) {
+                return;
+            }
+
+            let conn = self.sample_kit_backend.as_mut().map(|sample_kit| {
+                sample_kit.new_sample_connection(path, METRONOME_SAMPLE_CHANNEL)
+            });
+
+            self.metronome_sample = match conn {
+                Some(Ok(conn)) => Some((path.to_owned(), conn)),
+                Some(Err(err)) => {
+                    log::error!("{}", err);
+                    None
                 }
+                None => None,
+            };
+        }
+    }
+
+    /// Channel of the metronome clicks on MIDI outputs, `None` keeps them off.
+    pub fn set_metronome_midi_channel(&mut self, channel: Option<u8>) {
+        self.metronome_midi_channel = channel;
+    }
+
+    /// Note map applied to drum notes (guide notes and user hits) on their way out.
+    pub fn set_note_map(&mut self, note_map: Option<NoteMap>) {
+        self.note_map = note_map;
+    }
+
+    pub fn stop_all(&mut self) {
+        for (_, conn) in self.connections.iter_mut() {
+            conn.stop_all();
+        }
+
+        #[cfg(feature = "sample_kit")]
+        if let Some((_, conn)) = self.metronome_sample.as_mut() {
+            conn.stop_all();
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(conn) = self.virtual_output.as_mut() {
+            conn.stop_all();
+        }
+    }
+
+    pub fn midi_event(&mut self, group: OutputGroup, channel: u8, msg: MidiMessage) {
+        let msg = match (&self.note_map, group) {
+            (Some(note_map), OutputGroup::UserDrums | OutputGroup::GuideDrums) if channel == 9 => {
+                note_map.map_message(msg)
             }
+            _ => msg,
+        };
+
+        #[cfg(target_os = "linux")]
+        if let (Some(conn), OutputGroup::GuideDrums | OutputGroup::Music) =
+            (self.virtual_output.as_mut(), group)
+        {
+            conn.midi_event(channel, msg);
         }
+
+        if let Some(conn) = self.connection(group) {
+            conn.midi_event(channel, msg);
+        }
+    }
+
+    /// Send a click of the metronome, which never shares a channel with the song.
+    pub fn metronome_event(&mut self, msg: MidiMessage) {
+        #[cfg(feature = "sample_kit")]
+        if let Some((_, conn)) = self.metronome_sample.as_mut() {
+            conn.midi_event(METRONOME_SAMPLE_CHANNEL, msg);
+            return;
+        }
+
+        let midi_channel = self.metronome_midi_channel;
+        if let Some(conn) = self.connection(OutputGroup::Metronome) {
+            conn.metronome_event(midi_channel, msg);
+        }
+    }
+
+    /// Whether `a` and `b` play through the same connection.
+    pub fn shares_connection(&self, a: OutputGroup, b: OutputGroup) -> bool {
+        let desc = |group| self.routes.get(&group).unwrap_or(&self.output);
+        desc(a) == desc(b)
     }
 
-    pub fn midi_event(&mut self, channel: u8, msg: MidiMessage) {
-        self.output_connection.1.midi_event(channel, msg);
+    fn connection(&mut self, group: OutputGroup) -> Option<&mut Box<dyn OutputConnection>> {
+        let desc = self.routes.get(&group).unwrap_or(&self.output);
+        self.connections
+            .iter_mut()
+            .find(|(d, _)| d == desc)
+            .map(|(_, conn)| conn)
     }
 }
@@ -0,0 +1,193 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use midly::{num::u7, MidiMessage};
+use serde::{Deserialize, Serialize};
+
+/// Rewrites General MIDI drum notes into the notes expected by a drum plugin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteMap {
+    pub name: String,
+    /// GM note -> plugin note, notes that are not listed are sent unchanged.
+    #[serde(default)]
+    pub notes: HashMap<u8, u8>,
+}
+
+impl NoteMap {
+    pub fn general_midi() -> Self {
+        Self {
+            name: "General MIDI".into(),
+            notes: HashMap::new(),
+        }
+    }
+
+    /// General MIDI and the user maps found in the `note_maps` directory.
+    pub fn all() -> Vec<NoteMap> {
+        let mut maps = vec![Self::general_midi()];
+
+        if let Some(dir) = crate::utils::resources::note_maps_dir() {
+            maps.append(&mut Self::load_dir(&dir));
+        }
+
+        maps
+    }
+
+    /// Every `*.ron` map of `dir`, sorted by name.
+    fn load_dir(dir: &Path) -> Vec<NoteMap> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut maps: Vec<NoteMap> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map(|ext| ext == "ron").unwrap_or(false))
+            .filter_map(|path| {
+                let file = std::fs::read_to_string(&path).ok()?;
+                match ron::from_str(&file) {
+                    Ok(map) => Some(map),
+                    Err(err) => {
+                        log::error!("{:?}: {:#?}", path, err);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        maps.sort_by(|a, b| a.name.cmp(&b.name));
+        maps
+    }
+
+    pub fn map_note(&self, note: u7) -> u7 {
+        self.notes
+            .get(&note.as_int())
+            .and_then(|note| u7::try_from(*note))
+            .unwrap_or(note)
+    }
+
+    pub fn map_message(&self, message: MidiMessage) -> MidiMessage {
+        match message {
+            MidiMessage::NoteOn { key, vel } => MidiMessage::NoteOn {
+                key: self.map_note(key),
+                vel,
+            },
+            MidiMessage::NoteOff { key, vel } => MidiMessage::NoteOff {
+                key: self.map_note(key),
+                vel,
+            },
+            MidiMessage::Aftertouch { key, vel } => MidiMessage::Aftertouch {
+                key: self.map_note(key),
+                vel,
+            },
+            message => message,
+        }
+    }
+}
+
+impl fmt::Display for NoteMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(key: u8) -> MidiMessage {
+        MidiMessage::NoteOn {
+            key: u7::new(key),
+            vel: u7::new(100),
+        }
+    }
+
+    fn test_map() -> NoteMap {
+        ron::from_str(
+            r#"(
+                name: "Test Kit",
+                notes: {
+                    // Open hi-hat
+                    46: 26,
+                    // Out of the MIDI range
+                    59: 200,
+                },
+            )"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_ron() {
+        let map = test_map();
+        assert_eq!(map.name, "Test Kit");
+        assert_eq!(map.notes.len(), 2);
+
+        let empty: NoteMap = ron::from_str(r#"(name: "Empty")"#).unwrap();
+        assert!(empty.notes.is_empty());
+    }
+
+    #[test]
+    fn maps_listed_notes() {
+        let map = test_map();
+        assert_eq!(map.map_message(note_on(46)), note_on(26));
+        assert_eq!(
+            map.map_message(MidiMessage::NoteOff {
+                key: u7::new(46),
+                vel: u7::new(0),
+            }),
+            MidiMessage::NoteOff {
+                key: u7::new(26),
+                vel: u7::new(0),
+            }
+        );
+        assert_eq!(
+            map.map_message(MidiMessage::Aftertouch {
+                key: u7::new(46),
+                vel: u7::new(127),
+            }),
+            MidiMessage::Aftertouch {
+                key: u7::new(26),
+                vel: u7::new(127),
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_other_notes_and_messages() {
+        let map = test_map();
+        assert_eq!(map.map_message(note_on(38)), note_on(38));
+        assert_eq!(map.map_message(note_on(59)), note_on(59));
+
+        let controller = MidiMessage::Controller {
+            controller: u7::new(46),
+            value: u7::new(1),
+        };
+        assert_eq!(map.map_message(controller), controller);
+
+        let general_midi = NoteMap::general_midi();
+        assert_eq!(general_midi.map_message(note_on(46)), note_on(46));
+    }
+
+    #[test]
+    fn loads_ron_files_of_dir() {
+        let dir = std::env::temp_dir().join(format!("note_maps_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::write(dir.join("b.ron"), r#"(name: "B", notes: { 35: 36 })"#).unwrap();
+        std::fs::write(dir.join("a.ron"), r#"(name: "A")"#).unwrap();
+        std::fs::write(dir.join("broken.ron"), "(name: ").unwrap();
+        std::fs::write(dir.join("notes.txt"), r#"(name: "Text")"#).unwrap();
+
+        let maps = NoteMap::load_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = maps.iter().map(|map| map.name.as_str()).collect();
+        assert_eq!(names, ["A", "B"]);
+        assert_eq!(maps[1].map_note(u7::new(35)), u7::new(36));
+    }
+
+    #[test]
+    fn missing_dir_has_no_maps() {
+        assert!(NoteMap::load_dir(Path::new("/nonexistent/note_maps")).is_empty());
+    }
+}
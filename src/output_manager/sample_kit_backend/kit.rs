@@ -0,0 +1,94 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::sampler::{ClipCache, Region};
+
+/// Kit manifest, a RON file stored next to the samples it lists.
+#[derive(Debug, Deserialize)]
+struct KitManifest {
+    pads: Vec<PadManifest>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PadManifest {
+    /// Notes (of the drum channel) that trigger the pad.
+    notes: Vec<u8>,
+    #[serde(default = "default_volume")]
+    volume: f32,
+    /// From -1.0 (left) to 1.0 (right).
+    #[serde(default)]
+    pan: f32,
+    /// Choke group the pad belongs to.
+    #[serde(default)]
+    choke_group: Option<u32>,
+    /// Choke groups silenced when the pad is hit, e.g. the closed hi-hat chokes the open one.
+    #[serde(default)]
+    chokes: Vec<u32>,
+    layers: Vec<LayerManifest>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LayerManifest {
+    /// Highest velocity played by this layer.
+    max_velocity: u8,
+    /// Round-robin samples, relative to the manifest.
+    samples: Vec<PathBuf>,
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+pub fn load(path: &Path) -> Result<Vec<Region>, Box<dyn Error>> {
+    let manifest: KitManifest = ron::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|err| format!("failed to parse {:?}: {}", path, err))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut clips = ClipCache::default();
+    let mut regions = Vec::new();
+
+    for mut pad in manifest.pads {
+        pad.layers.sort_by_key(|layer| layer.max_velocity);
+
+        let mut lovel = 0;
+        let layer_count = pad.layers.len();
+
+        for (layer_id, layer) in pad.layers.into_iter().enumerate() {
+            // Louder hits than the last layer still play it
+            let hivel = if layer_id + 1 == layer_count {
+                127
+            } else {
+                layer.max_velocity
+            };
+
+            let seq_length = layer.samples.len() as u32;
+            for (sample_id, sample) in layer.samples.iter().enumerate() {
+                let clip = clips.load(&dir.join(sample))?;
+
+                for note in pad.notes.iter() {
+                    regions.push(Region {
+                        clip: clip.clone(),
+                        lokey: *note,
+                        hikey: *note,
+                        lovel,
+                        hivel,
+                        seq_length,
+                        seq_position: sample_id as u32 + 1,
+                        volume: pad.volume,
+                        pan: pad.pan,
+                        chokes: pad.chokes.clone(),
+                        off_by: pad.choke_group,
+                    });
+                }
+            }
+
+            lovel = hivel.saturating_add(1);
+        }
+    }
+
+    Ok(regions)
+}
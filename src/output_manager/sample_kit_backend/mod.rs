@@ -0,0 +1,153 @@
+use std::{
+    error::Error,
+    path::Path,
+    rc::Rc,
+    sync::mpsc::{Receiver, Sender},
+};
+
+use crate::{
+    audio::{AudioOutput, AudioSource, AudioSourceHandle},
+    output_manager::{OutputConnection, OutputDescriptor},
+};
+
+use midly::{num::u7, MidiMessage};
+
+mod kit;
+mod sampler;
+mod sfz;
+use sampler::{ClipCache, Region, Sampler};
+
+/// Whether `path` is an SFZ instrument rather than a kit manifest.
+pub fn is_sfz(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some(ext) if ext.eq_ignore_ascii_case("sfz")
+    )
+}
+
+/// Sampler fed by the MIDI events of one connection.
+struct SamplerSource {
+    sampler: Sampler,
+    rx: Receiver<(u8, MidiMessage)>,
+    /// Only one channel has samples.
+    sampled_channel: u8,
+}
+
+impl AudioSource for SamplerSource {
+    fn render(&mut self, frames: &mut [(f32, f32)]) {
+        while let Ok((channel, message)) = self.rx.try_recv() {
+            if channel != self.sampled_channel {
+                continue;
+            }
+
+            match message {
+                MidiMessage::NoteOn { key, vel } if vel > 0 => {
+                    self.sampler.note_on(key.as_int(), vel.as_int());
+                }
+                // One-shot samples ring out, note offs are ignored
+                MidiMessage::NoteOn { .. } | MidiMessage::NoteOff { .. } => {}
+                MidiMessage::Aftertouch { key, vel } if vel > 0 => {
+                    self.sampler.choke(key.as_int());
+                }
+                MidiMessage::Controller { controller, value } => match controller.as_int() {
+                    // Channel Volume
+                    7 => self.sampler.set_volume(value.as_int()),
+                    // All Sound Off, All Notes Off
+                    120 | 123 => self.sampler.stop_all(),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        for frame in frames.iter_mut() {
+            *frame = self.sampler.next_frame();
+        }
+    }
+}
+
+pub struct SampleKitBackend {
+    output: Rc<AudioOutput>,
+}
+
+impl SampleKitBackend {
+    pub fn new(output: Rc<AudioOutput>) -> Self {
+        Self { output }
+    }
+
+    fn connect(&self, regions: Vec<Region>, channel: u8) -> SampleKitOutputConnection {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _source = self.output.add_source(SamplerSource {
+            sampler: Sampler::new(regions, self.output.sample_rate()),
+            rx,
+            sampled_channel: channel,
+        });
+
+        SampleKitOutputConnection {
+            _source,
+            tx,
+            channel,
+        }
+    }
+
+    /// Open the kit described by the manifest or SFZ instrument at `path`.
+    pub fn new_output_connection(
+        &mut self,
+        path: &Path,
+    ) -> Result<SampleKitOutputConnection, Box<dyn Error>> {
+        let regions = if is_sfz(path) {
+            sfz::load(path)?
+        } else {
+            kit::load(path)?
+        };
+
+        Ok(self.connect(regions, 9))
+    }
+
+    /// Play the sample at `path` on every note of `channel`, louder with velocity.
+    pub fn new_sample_connection(
+        &mut self,
+        path: &Path,
+        channel: u8,
+    ) -> Result<SampleKitOutputConnection, Box<dyn Error>> {
+        let region = Region {
+            clip: ClipCache::default().load(path)?,
+            lokey: 0,
+            hikey: 127,
+            lovel: 0,
+            hivel: 127,
+            seq_length: 1,
+            seq_position: 1,
+            volume: 1.0,
+            pan: 0.0,
+            chokes: Vec::new(),
+            off_by: None,
+        };
+
+        Ok(self.connect(vec![region], channel))
+    }
+
+    pub fn get_outputs(&self) -> Vec<OutputDescriptor> {
+        vec![OutputDescriptor::SampleKit(None)]
+    }
+}
+
+pub struct SampleKitOutputConnection {
+    _source: AudioSourceHandle,
+    tx: Sender<(u8, MidiMessage)>,
+    channel: u8,
+}
+
+impl OutputConnection for SampleKitOutputConnection {
+    fn midi_event(&mut self, channel: u8, message: MidiMessage) {
+        self.tx.send((channel, message)).ok();
+    }
+
+    fn stop_all(&mut self) {
+        let message = MidiMessage::Controller {
+            controller: u7::new(120),
+            value: u7::new(0),
+        };
+        self.tx.send((self.channel, message)).ok();
+    }
+}
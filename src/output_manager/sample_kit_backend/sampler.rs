@@ -0,0 +1,185 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    f32::consts::{FRAC_PI_4, SQRT_2},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::audio::Clip;
+
+/// Voices above this limit steal the oldest one.
+const MAX_VOICES: usize = 64;
+/// Length of the fade-out of a choked voice, in seconds, short enough to sound like a grab
+/// but long enough to avoid clicks.
+const CHOKE_FADE: f64 = 0.01;
+
+/// Sample played for a range of keys and velocities.
+pub struct Region {
+    pub clip: Arc<Clip>,
+    pub lokey: u8,
+    pub hikey: u8,
+    pub lovel: u8,
+    pub hivel: u8,
+    /// Round-robin: the region plays on the `seq_position`th of every `seq_length` hits (1 based).
+    pub seq_length: u32,
+    pub seq_position: u32,
+    /// Linear gain.
+    pub volume: f32,
+    /// From -1.0 (left) to 1.0 (right).
+    pub pan: f32,
+    /// Choke groups silenced when the region plays.
+    pub chokes: Vec<u32>,
+    /// Choke group that silences the region.
+    pub off_by: Option<u32>,
+}
+
+impl Region {
+    fn matches(&self, key: u8, vel: u8, hit: u32) -> bool {
+        (self.lokey..=self.hikey).contains(&key)
+            && (self.lovel..=self.hivel).contains(&vel)
+            && hit % self.seq_length.max(1) + 1 == self.seq_position
+    }
+}
+
+/// Decoded samples shared by every region that lists the same file.
+#[derive(Default)]
+pub struct ClipCache {
+    clips: HashMap<PathBuf, Arc<Clip>>,
+}
+
+impl ClipCache {
+    pub fn load(&mut self, path: &Path) -> Result<Arc<Clip>, Box<dyn Error>> {
+        if let Some(clip) = self.clips.get(path) {
+            return Ok(clip.clone());
+        }
+
+        let clip = Clip::load(path).map_err(|err| format!("failed to load {:?}: {}", path, err))?;
+        let clip = Arc::new(clip);
+        self.clips.insert(path.to_owned(), clip.clone());
+
+        Ok(clip)
+    }
+}
+
+struct Voice {
+    clip: Arc<Clip>,
+    /// Position in the clip, in seconds.
+    position: f64,
+    gain_l: f32,
+    gain_r: f32,
+    key: u8,
+    off_by: Option<u32>,
+    /// Remaining gain of a choked voice.
+    fade: Option<f32>,
+}
+
+/// Plays the regions of a kit on the drum channel.
+pub struct Sampler {
+    regions: Vec<Region>,
+    /// Hits per key, drives the round-robin.
+    hits: HashMap<u8, u32>,
+    voices: Vec<Voice>,
+    /// Set by the channel volume controller.
+    gain: f32,
+    /// Output seconds per frame.
+    step: f64,
+    fade_step: f32,
+}
+
+impl Sampler {
+    pub fn new(regions: Vec<Region>, sample_rate: u32) -> Self {
+        Self {
+            regions,
+            hits: HashMap::new(),
+            voices: Vec::new(),
+            gain: 1.0,
+            step: 1.0 / sample_rate as f64,
+            fade_step: (1.0 / (CHOKE_FADE * sample_rate as f64)) as f32,
+        }
+    }
+
+    pub fn note_on(&mut self, key: u8, vel: u8) {
+        let hits = self.hits.entry(key).or_default();
+        let hit = *hits;
+        *hits = hits.wrapping_add(1);
+
+        let regions: Vec<&Region> = self
+            .regions
+            .iter()
+            .filter(|region| region.matches(key, vel, hit))
+            .collect();
+
+        for voice in self.voices.iter_mut() {
+            let choked = matches!(
+                voice.off_by,
+                Some(group) if regions.iter().any(|r| r.chokes.contains(&group))
+            );
+            if choked {
+                voice.fade.get_or_insert(1.0);
+            }
+        }
+
+        let vel = vel as f32 / 127.0;
+
+        for region in regions {
+            // Equal power panning, unity gain in the center
+            let angle = (region.pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+            let gain = region.volume * vel * SQRT_2;
+
+            if self.voices.len() >= MAX_VOICES {
+                self.voices.remove(0);
+            }
+            self.voices.push(Voice {
+                clip: region.clip.clone(),
+                position: 0.0,
+                gain_l: angle.cos() * gain,
+                gain_r: angle.sin() * gain,
+                key,
+                off_by: region.off_by,
+                fade: None,
+            });
+        }
+    }
+
+    /// Silence what `key` is playing, like grabbing a cymbal.
+    pub fn choke(&mut self, key: u8) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.key == key) {
+            voice.fade.get_or_insert(1.0);
+        }
+    }
+
+    pub fn set_volume(&mut self, value: u8) {
+        self.gain = value as f32 / 127.0;
+    }
+
+    pub fn stop_all(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.fade.get_or_insert(1.0);
+        }
+    }
+
+    pub fn next_frame(&mut self) -> (f32, f32) {
+        let (mut l, mut r) = (0.0, 0.0);
+
+        for voice in self.voices.iter_mut() {
+            let (sample_l, sample_r) = voice.clip.frame_at(voice.position);
+            let fade = voice.fade.unwrap_or(1.0);
+
+            l += sample_l * voice.gain_l * fade;
+            r += sample_r * voice.gain_r * fade;
+
+            voice.position += self.step;
+            if let Some(fade) = voice.fade.as_mut() {
+                *fade -= self.fade_step;
+            }
+        }
+
+        self.voices.retain(|voice| {
+            voice.position < voice.clip.duration()
+                && !matches!(voice.fade, Some(fade) if fade <= 0.0)
+        });
+
+        (l * self.gain, r * self.gain)
+    }
+}
@@ -0,0 +1,564 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use super::sampler::{ClipCache, Region};
+
+/// Guards against `#include` loops.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+type Opcodes = HashMap<String, String>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Header {
+    Control,
+    Global,
+    Master,
+    Group,
+    Region,
+    /// Headers that do not describe regions (`<curve>`, `<effect>`, ...).
+    Other,
+}
+
+/// Opcodes in scope while the file is read, inner headers override outer ones.
+#[derive(Default)]
+struct Scope {
+    control: Opcodes,
+    global: Opcodes,
+    master: Opcodes,
+    group: Opcodes,
+    region: Option<Opcodes>,
+}
+
+impl Scope {
+    fn opcodes(&mut self, header: Header) -> Option<&mut Opcodes> {
+        match header {
+            Header::Control => Some(&mut self.control),
+            Header::Global => Some(&mut self.global),
+            Header::Master => Some(&mut self.master),
+            Header::Group => Some(&mut self.group),
+            Header::Region => self.region.as_mut(),
+            Header::Other => None,
+        }
+    }
+
+    /// Opcodes of the current region, including inherited ones.
+    fn region(&self) -> Option<Opcodes> {
+        let region = self.region.as_ref()?;
+
+        let mut opcodes = self.global.clone();
+        opcodes.extend(self.master.clone());
+        opcodes.extend(self.group.clone());
+        opcodes.extend(region.clone());
+
+        Some(opcodes)
+    }
+}
+
+/// Regions of the SFZ instrument at `path`.
+///
+/// Covers what drum kits rely on: key and velocity ranges, round-robin
+/// (`seq_length`/`seq_position`), choke groups (`group`/`off_by`), `volume` and `pan`.
+/// Pitch, envelopes and filters are ignored.
+pub fn load(path: &Path) -> Result<Vec<Region>, Box<dyn Error>> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut text = String::new();
+    preprocess(path, dir, &mut HashMap::new(), &mut text, 0)?;
+
+    let mut clips = ClipCache::default();
+    let mut regions = Vec::new();
+
+    let mut scope = Scope::default();
+    let mut header = Header::Other;
+
+    for token in tokenize(&text) {
+        match token {
+            Token::Header(name) => {
+                if let Some(opcodes) = scope.region() {
+                    push_region(&mut regions, &mut clips, dir, &scope.control, &opcodes)?;
+                }
+                scope.region = None;
+
+                header = match name {
+                    "control" => Header::Control,
+                    "global" => Header::Global,
+                    "master" => Header::Master,
+                    "group" => Header::Group,
+                    "region" => Header::Region,
+                    _ => Header::Other,
+                };
+
+                // A new header resets its own scope and the ones nested in it
+                match header {
+                    Header::Control => scope.control.clear(),
+                    Header::Global => {
+                        scope.global.clear();
+                        scope.master.clear();
+                        scope.group.clear();
+                    }
+                    Header::Master => {
+                        scope.master.clear();
+                        scope.group.clear();
+                    }
+                    Header::Group => scope.group.clear(),
+                    Header::Region => scope.region = Some(Opcodes::new()),
+                    Header::Other => {}
+                }
+            }
+            Token::Opcode(name, value) => {
+                if let Some(opcodes) = scope.opcodes(header) {
+                    opcodes.insert(name.to_owned(), value.to_owned());
+                }
+            }
+        }
+    }
+
+    if let Some(opcodes) = scope.region() {
+        push_region(&mut regions, &mut clips, dir, &scope.control, &opcodes)?;
+    }
+
+    Ok(regions)
+}
+
+fn push_region(
+    regions: &mut Vec<Region>,
+    clips: &mut ClipCache,
+    dir: &Path,
+    control: &Opcodes,
+    opcodes: &Opcodes,
+) -> Result<(), Box<dyn Error>> {
+    let sample = match opcodes.get("sample") {
+        Some(sample) => sample,
+        None => return Ok(()),
+    };
+
+    // Built-in generators like `*sine` have no file
+    if sample.starts_with('*') {
+        return Ok(());
+    }
+
+    // Release samples and other triggers are not supported
+    if matches!(opcodes.get("trigger"), Some(trigger) if trigger != "attack") {
+        return Ok(());
+    }
+
+    let mut file = PathBuf::from(dir);
+    if let Some(default_path) = control.get("default_path") {
+        file.push(default_path.replace('\\', "/"));
+    }
+    file.push(sample.replace('\\', "/"));
+
+    let key = |name: &str| opcodes.get(name).and_then(|value| parse_key(value));
+    let int = |name: &str| {
+        opcodes
+            .get(name)
+            .and_then(|value| value.parse::<i64>().ok())
+    };
+    let float = |name: &str| {
+        opcodes
+            .get(name)
+            .and_then(|value| value.parse::<f32>().ok())
+    };
+    let vel = |name: &str| int(name).map(|value| value.clamp(0, 127) as u8);
+    let group = |name: &str| {
+        int(name)
+            .filter(|group| *group > 0)
+            .map(|group| group as u32)
+    };
+
+    let (lokey, hikey) = match key("key") {
+        Some(key) => (key, key),
+        None => (key("lokey").unwrap_or(0), key("hikey").unwrap_or(127)),
+    };
+
+    regions.push(Region {
+        clip: clips.load(&file)?,
+        lokey,
+        hikey,
+        lovel: vel("lovel").unwrap_or(0),
+        hivel: vel("hivel").unwrap_or(127),
+        seq_length: int("seq_length").unwrap_or(1).max(1) as u32,
+        seq_position: int("seq_position").unwrap_or(1).max(1) as u32,
+        volume: 10f32.powf(float("volume").unwrap_or(0.0) / 20.0),
+        pan: float("pan").unwrap_or(0.0) / 100.0,
+        chokes: group("group").into_iter().collect(),
+        off_by: group("off_by"),
+    });
+
+    Ok(())
+}
+
+/// Text of `path` without comments, with `#include`s inlined and `#define`s substituted.
+fn preprocess(
+    path: &Path,
+    dir: &Path,
+    defines: &mut HashMap<String, String>,
+    out: &mut String,
+    depth: usize,
+) -> Result<(), Box<dyn Error>> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(format!("too many nested includes in {:?}", path).into());
+    }
+
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| format!("failed to read {:?}: {}", path, err))?;
+    let text = strip_block_comments(&text);
+
+    for line in text.lines() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+
+        if let Some(include) = line.strip_prefix("#include") {
+            // Includes are relative to the top level file
+            let include = include.trim().trim_matches('"').replace('\\', "/");
+            preprocess(&dir.join(include), dir, defines, out, depth + 1)?;
+        } else if let Some(define) = line.strip_prefix("#define") {
+            let mut parts = define.trim().splitn(2, char::is_whitespace);
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                defines.insert(name.to_owned(), value.trim().to_owned());
+            }
+        } else {
+            let mut line = line.to_owned();
+            if line.contains('$') {
+                // Longest names first, so `$KICK` does not eat the start of `$KICK2`
+                let mut names: Vec<&String> = defines.keys().collect();
+                names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+                for name in names {
+                    line = line.replace(name.as_str(), &defines[name]);
+                }
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+
+    Ok(())
+}
+
+fn strip_block_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start..].find("*/") {
+            Some(end) => &rest[start + end + 2..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+
+    out
+}
+
+enum Token<'a> {
+    Header(&'a str),
+    Opcode(&'a str, &'a str),
+}
+
+/// Splits preprocessed text into headers and opcodes,
+/// opcode values run until the next opcode so sample paths can contain spaces.
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+
+    for line in text.lines() {
+        let mut rest = line;
+
+        while !rest.is_empty() {
+            rest = rest.trim_start();
+
+            if let Some(header) = rest.strip_prefix('<') {
+                let end = header.find('>').unwrap_or(header.len());
+                tokens.push(Token::Header(header[..end].trim()));
+                rest = header.get(end + 1..).unwrap_or_default();
+                continue;
+            }
+
+            let eq = match rest.find('=') {
+                Some(eq) => eq,
+                None => break,
+            };
+            let name = rest[..eq].trim();
+            let value = &rest[eq + 1..];
+
+            // The value ends before the next `name=` or header
+            let end = next_token_start(value).unwrap_or(value.len());
+            tokens.push(Token::Opcode(name, value[..end].trim()));
+            rest = &value[end..];
+        }
+    }
+
+    tokens
+}
+
+fn next_token_start(value: &str) -> Option<usize> {
+    let bytes = value.as_bytes();
+
+    for (id, byte) in bytes.iter().enumerate() {
+        if *byte == b'<' {
+            return Some(id);
+        }
+        if !byte.is_ascii_whitespace() {
+            continue;
+        }
+
+        let word = &value[id + 1..];
+        let name_len = word
+            .bytes()
+            .take_while(|b| b.is_ascii_alphanumeric() || *b == b'_')
+            .count();
+        if name_len > 0 && word.as_bytes().get(name_len) == Some(&b'=') {
+            return Some(id);
+        }
+    }
+
+    None
+}
+
+/// MIDI note from a number or a note name (`c4` is 60).
+fn parse_key(value: &str) -> Option<u8> {
+    if let Ok(key) = value.parse::<i32>() {
+        return u8::try_from(key).ok().filter(|key| *key < 128);
+    }
+
+    let value = value.to_ascii_lowercase();
+    let mut chars = value.chars();
+
+    let mut semitone = match chars.next()? {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+
+    let rest = chars.as_str();
+    let octave = if let Some(rest) = rest.strip_prefix('#') {
+        semitone += 1;
+        rest
+    } else if let Some(rest) = rest.strip_prefix('b').filter(|rest| !rest.is_empty()) {
+        semitone -= 1;
+        rest
+    } else {
+        rest
+    };
+
+    let key = (octave.parse::<i32>().ok()? + 1) * 12 + semitone;
+    u8::try_from(key).ok().filter(|key| *key < 128)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Directory of a test instrument, removed once dropped.
+    struct TestKit {
+        dir: PathBuf,
+    }
+
+    impl TestKit {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("sfz_test_{}_{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self { dir }
+        }
+
+        /// A few frames of silence, as a 16 bit mono WAV.
+        fn sample(&self, name: &str) -> &Self {
+            let frames = 8u32;
+            let mut wav = Vec::new();
+            wav.extend(b"RIFF");
+            wav.extend((36 + frames * 2).to_le_bytes());
+            wav.extend(b"WAVEfmt ");
+            wav.extend(16u32.to_le_bytes());
+            wav.extend(1u16.to_le_bytes());
+            wav.extend(1u16.to_le_bytes());
+            wav.extend(44100u32.to_le_bytes());
+            wav.extend((44100u32 * 2).to_le_bytes());
+            wav.extend(2u16.to_le_bytes());
+            wav.extend(16u16.to_le_bytes());
+            wav.extend(b"data");
+            wav.extend((frames * 2).to_le_bytes());
+            wav.extend(vec![0; frames as usize * 2]);
+
+            let path = self.dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, wav).unwrap();
+            self
+        }
+
+        fn load(&self, name: &str, sfz: &str) -> Result<Vec<Region>, Box<dyn Error>> {
+            let path = self.dir.join(name);
+            std::fs::write(&path, sfz).unwrap();
+            load(&path)
+        }
+    }
+
+    impl Drop for TestKit {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.dir).ok();
+        }
+    }
+
+    fn db(volume: f32) -> f32 {
+        10f32.powf(volume / 20.0)
+    }
+
+    #[test]
+    fn regions_inherit_outer_opcodes() {
+        let kit = TestKit::new("inherit");
+        kit.sample("samples/kick 1.wav");
+
+        let regions = kit
+            .load(
+                "kit.sfz",
+                "<control> default_path=samples\\
+                 <global> volume=-6 pan=10
+                 <master> lovel=10
+                 <group> group=1 off_by=2
+                 <region> sample=kick 1.wav key=36 volume=0
+                 <region> sample=kick 1.wav lokey=c4 hikey=d#4
+                 <group>
+                 <region> sample=kick 1.wav key=38",
+            )
+            .unwrap();
+
+        assert_eq!(regions.len(), 3);
+
+        // Region opcodes override the ones of the outer headers
+        assert_eq!((regions[0].lokey, regions[0].hikey), (36, 36));
+        assert_eq!(regions[0].volume, 1.0);
+        assert_eq!(regions[0].pan, 0.1);
+        assert_eq!((regions[0].lovel, regions[0].hivel), (10, 127));
+        assert_eq!(regions[0].chokes, [1]);
+        assert_eq!(regions[0].off_by, Some(2));
+
+        assert_eq!((regions[1].lokey, regions[1].hikey), (60, 63));
+        assert_eq!(regions[1].volume, db(-6.0));
+        assert_eq!(regions[1].chokes, [1]);
+
+        // A new group drops the opcodes of the previous one, but not the master ones
+        assert_eq!(regions[2].lovel, 10);
+        assert_eq!(regions[2].pan, 0.1);
+        assert!(regions[2].chokes.is_empty());
+        assert_eq!(regions[2].off_by, None);
+
+        // The sample is decoded once
+        assert!(Arc::ptr_eq(&regions[0].clip, &regions[2].clip));
+    }
+
+    #[test]
+    fn new_master_resets_groups() {
+        let kit = TestKit::new("master");
+        kit.sample("snare.wav");
+
+        let regions = kit
+            .load(
+                "kit.sfz",
+                "<master> volume=-6 <group> group=3
+                 <master> <region> sample=snare.wav",
+            )
+            .unwrap();
+
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].volume, 1.0);
+        assert!(regions[0].chokes.is_empty());
+    }
+
+    #[test]
+    fn preprocesses_comments_defines_and_includes() {
+        let kit = TestKit::new("preprocess");
+        kit.sample("snare.wav");
+        std::fs::write(
+            kit.dir.join("snare.sfz"),
+            "<region> sample=snare.wav key=$SNARE seq_length=2 seq_position=2",
+        )
+        .unwrap();
+
+        let regions = kit
+            .load(
+                "kit.sfz",
+                "/* Test kit,
+                    two lines long */
+                 #define $SNARE 38 // Acoustic snare
+                 #include \"snare.sfz\"",
+            )
+            .unwrap();
+
+        assert_eq!(regions.len(), 1);
+        assert_eq!((regions[0].lokey, regions[0].hikey), (38, 38));
+        assert_eq!((regions[0].seq_length, regions[0].seq_position), (2, 2));
+    }
+
+    #[test]
+    fn skips_regions_without_sample_file() {
+        let kit = TestKit::new("skip");
+        kit.sample("hit.wav");
+
+        let regions = kit
+            .load(
+                "kit.sfz",
+                "<region> key=36
+                 <region> sample=*sine key=37
+                 <region> sample=hit.wav key=38 trigger=release
+                 <curve> sample=hit.wav",
+            )
+            .unwrap();
+
+        assert!(regions.is_empty());
+    }
+
+    #[test]
+    fn ignores_malformed_text() {
+        let kit = TestKit::new("malformed");
+        kit.sample("hit.wav");
+
+        let regions = kit
+            .load(
+                "kit.sfz",
+                "<region> sample=hit.wav key=36
+                 this line is not sfz
+                 <region sample=hit.wav key=40
+                 <region> sample=hit.wav key=not_a_key lovel=-5 hivel=300
+                 /* unterminated <region> sample=hit.wav key=42",
+            )
+            .unwrap();
+
+        assert_eq!(regions.len(), 2);
+        assert_eq!((regions[0].lokey, regions[0].hikey), (36, 36));
+        assert_eq!((regions[1].lokey, regions[1].hikey), (0, 127));
+        assert_eq!((regions[1].lovel, regions[1].hivel), (0, 127));
+    }
+
+    #[test]
+    fn missing_sample_is_an_error() {
+        let kit = TestKit::new("missing");
+        assert!(kit.load("kit.sfz", "<region> sample=missing.wav").is_err());
+    }
+
+    #[test]
+    fn include_loop_is_an_error() {
+        let kit = TestKit::new("loop");
+        assert!(kit.load("kit.sfz", "#include \"kit.sfz\"").is_err());
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(parse_key("36"), Some(36));
+        assert_eq!(parse_key("c4"), Some(60));
+        assert_eq!(parse_key("C#4"), Some(61));
+        assert_eq!(parse_key("eb4"), Some(63));
+        assert_eq!(parse_key("c-1"), Some(0));
+        assert_eq!(parse_key("g9"), Some(127));
+        assert_eq!(parse_key("128"), None);
+        assert_eq!(parse_key("h4"), None);
+    }
+}
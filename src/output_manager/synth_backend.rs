@@ -1,60 +1,91 @@
-use std::{error::Error, path::Path, sync::mpsc::Receiver, time::Duration};
+use std::{
+    error::Error,
+    path::Path,
+    rc::Rc,
+    sync::mpsc::{Receiver, Sender},
+    time::Duration,
+};
 
-use crate::output_manager::{OutputConnection, OutputDescriptor};
+use crate::{
+    audio::{AudioOutput, AudioSourceHandle},
+    config::SynthEffectsConfig,
+    output_manager::{OutputConnection, OutputDescriptor},
+};
 
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use lib_midi::MidiEvent;
 use midly::MidiMessage;
 
-pub struct SynthBackend {
-    _host: cpal::Host,
-    device: cpal::Device,
+/// Stereo width of the reverb, not exposed in the settings.
+const REVERB_WIDTH: f32 = 0.5;
+/// The synth has channels past the 16 of MIDI, the metronome gets the first of them
+/// so it never takes the place of an instrument of the song.
+const MIDI_CHANNELS: u8 = 32;
+const SYNTH_CLICK_CHANNEL: u8 = 16;
 
-    stream_config: cpal::StreamConfig,
-    sample_format: cpal::SampleFormat,
+pub struct SynthBackend {
+    output: Rc<AudioOutput>,
+    effects: SynthEffectsConfig,
+    /// Effect updates of the open connections.
+    effect_senders: Vec<Sender<SynthEffectsConfig>>,
 }
 
 impl SynthBackend {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
-        let host = cpal::default_host();
-
-        let device = host
-            .default_output_device()
-            .ok_or("failed to find a default output device")?;
-
-        let config = device.default_output_config()?;
-        let sample_format = config.sample_format();
-
-        let stream_config: cpal::StreamConfig = config.into();
-
-        Ok(Self {
-            _host: host,
-            device,
+    pub fn new(output: Rc<AudioOutput>, effects: &SynthEffectsConfig) -> Self {
+        Self {
+            output,
+            effects: effects.clone(),
+            effect_senders: Vec::new(),
+        }
+    }
 
-            stream_config,
-            sample_format,
-        })
+    /// Apply new effect settings to the running synths, without reloading the SoundFont.
+    pub fn set_effects(&mut self, effects: &SynthEffectsConfig) {
+        self.effects = effects.clone();
+        self.effect_senders
+            .retain(|tx| tx.send(effects.clone()).is_ok());
     }
 
-    fn run<T: cpal::Sample>(&self, rx: Receiver<MidiEvent>, path: &Path) -> cpal::Stream {
-        let mut next_value = {
-            let sample_rate = self.stream_config.sample_rate.0 as f32;
+    fn run(
+        &self,
+        rx: Receiver<MidiEvent>,
+        effects_rx: Receiver<SynthEffectsConfig>,
+        path: &Path,
+    ) -> Result<AudioSourceHandle, Box<dyn Error>> {
+        let next_value = {
+            let sample_rate = self.output.sample_rate() as f32;
 
             let mut synth = oxisynth::Synth::new(oxisynth::SynthDescriptor {
                 sample_rate,
-                gain: 0.3,
+                gain: self.effects.master_gain,
+                midi_channels: MIDI_CHANNELS,
                 ..Default::default()
             })
-            .unwrap();
+            .map_err(|err| format!("failed to create synth: {:?}", err))?;
 
-            let mut file = std::fs::File::open(path).unwrap();
-            let font = oxisynth::SoundFont::load(&mut file).unwrap();
+            let mut file = std::fs::File::open(path)?;
+            let font = oxisynth::SoundFont::load(&mut file)
+                .map_err(|err| format!("failed to load {:?}: {:?}", path, err))?;
             synth.add_font(font, true);
             synth.set_sample_rate(sample_rate);
             synth.program_reset();
 
+            let mut effects = self.effects.clone();
+            apply_effects(&mut synth, &effects);
+
+            let mut limiter = Limiter::new(self.output.sample_rate());
+
             move || {
+                if let Ok(new_effects) = effects_rx.try_recv() {
+                    effects = new_effects;
+                    apply_effects(&mut synth, &effects);
+                }
+
                 let (l, r) = synth.read_next();
+                let (l, r) = if effects.limiter {
+                    limiter.process(l, r)
+                } else {
+                    (l, r)
+                };
 
                 if let Ok(evt) = rx.try_recv() {
                     let channel = evt.channel;
@@ -67,6 +98,9 @@ impl SynthBackend {
                                 })
                                 .ok();
                         }
+                        // Effect sends come from the settings, not from the song
+                        MidiMessage::Controller { controller, .. }
+                            if controller == REVERB_SEND || controller == CHORUS_SEND => {}
                         MidiMessage::Controller { controller, value } => {
                             synth
                                 .send_event(oxisynth::MidiEvent::ControlChange {
@@ -93,8 +127,31 @@ impl SynthBackend {
                                 })
                                 .ok();
                         }
-                        _ => {
-                            log::warn!("implement missing midi messages {:?}", evt.message)
+                        // SoundFonts have no notion of chokes, a grabbed cymbal is released instead
+                        MidiMessage::Aftertouch { key, vel } if vel > 0 => {
+                            synth
+                                .send_event(oxisynth::MidiEvent::NoteOff {
+                                    channel,
+                                    key: key.as_int(),
+                                })
+                                .ok();
+                        }
+                        MidiMessage::Aftertouch { .. } => {}
+                        MidiMessage::ChannelAftertouch { vel } => {
+                            synth
+                                .send_event(oxisynth::MidiEvent::ChannelPressure {
+                                    channel,
+                                    value: vel.as_int(),
+                                })
+                                .ok();
+                        }
+                        MidiMessage::PitchBend { bend } => {
+                            synth
+                                .send_event(oxisynth::MidiEvent::PitchBend {
+                                    channel,
+                                    value: bend.0.as_int(),
+                                })
+                                .ok();
                         }
                     }
                 }
@@ -103,54 +160,97 @@ impl SynthBackend {
             }
         };
 
-        let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
+        Ok(self.output.add_source(next_value))
+    }
 
-        let channels = self.stream_config.channels as usize;
+    pub fn new_output_connection(
+        &mut self,
+        path: &Path,
+    ) -> Result<SynthOutputConnection, Box<dyn Error>> {
+        let (tx, rx) = std::sync::mpsc::channel::<MidiEvent>();
+        let (effects_tx, effects_rx) = std::sync::mpsc::channel();
+        let _source = self.run(rx, effects_rx, path)?;
+        self.effect_senders.push(effects_tx);
 
-        let stream = self
-            .device
-            .build_output_stream(
-                &self.stream_config,
-                move |output: &mut [T], _: &cpal::OutputCallbackInfo| {
-                    for frame in output.chunks_mut(channels) {
-                        let (l, r) = next_value();
+        Ok(SynthOutputConnection { _source, tx })
+    }
 
-                        let l: T = cpal::Sample::from::<f32>(&l);
-                        let r: T = cpal::Sample::from::<f32>(&r);
+    pub fn get_outputs(&self) -> Vec<OutputDescriptor> {
+        vec![OutputDescriptor::Synth(None)]
+    }
+}
 
-                        let channels = [l, r];
+const REVERB_SEND: u8 = 91;
+const CHORUS_SEND: u8 = 93;
 
-                        for (id, sample) in frame.iter_mut().enumerate() {
-                            *sample = channels[id % 2];
-                        }
-                    }
-                },
-                err_fn,
-            )
-            .unwrap();
-        stream.play().unwrap();
+fn apply_effects(synth: &mut oxisynth::Synth, effects: &SynthEffectsConfig) {
+    synth.set_gain(effects.master_gain);
+    synth.set_reverb_params(
+        effects.reverb_room_size,
+        effects.reverb_damping,
+        REVERB_WIDTH,
+        effects.reverb_level,
+    );
 
-        stream
+    for channel in (0..16).chain([SYNTH_CLICK_CHANNEL]) {
+        let (reverb, chorus) = match channel {
+            9 => (effects.drums_reverb, 0),
+            // Clicks stay dry
+            SYNTH_CLICK_CHANNEL => (0, 0),
+            _ => (effects.music_reverb, effects.chorus),
+        };
+
+        for (ctrl, value) in [(REVERB_SEND, reverb), (CHORUS_SEND, chorus)] {
+            synth
+                .send_event(oxisynth::MidiEvent::ControlChange {
+                    channel,
+                    ctrl,
+                    value: value.min(127),
+                })
+                .ok();
+        }
     }
+}
 
-    pub fn new_output_connection(&mut self, path: &Path) -> SynthOutputConnection {
-        let (tx, rx) = std::sync::mpsc::channel::<MidiEvent>();
-        let _stream = match self.sample_format {
-            cpal::SampleFormat::F32 => self.run::<f32>(rx, path),
-            cpal::SampleFormat::I16 => self.run::<i16>(rx, path),
-            cpal::SampleFormat::U16 => self.run::<u16>(rx, path),
-        };
+/// Peak limiter, turns the gain down as soon as a frame would go over the threshold
+/// and brings it back up slowly.
+struct Limiter {
+    gain: f32,
+    release_step: f32,
+}
 
-        SynthOutputConnection { _stream, tx }
+impl Limiter {
+    const THRESHOLD: f32 = 0.95;
+    /// Time the gain takes to recover from a full reduction, in seconds.
+    const RELEASE: f32 = 0.2;
+
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            gain: 1.0,
+            release_step: 1.0 / (Self::RELEASE * sample_rate as f32),
+        }
     }
 
-    pub fn get_outputs(&self) -> Vec<OutputDescriptor> {
-        vec![OutputDescriptor::Synth(None)]
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        let peak = l.abs().max(r.abs());
+        let target = if peak > Self::THRESHOLD {
+            Self::THRESHOLD / peak
+        } else {
+            1.0
+        };
+
+        self.gain = if target < self.gain {
+            target
+        } else {
+            (self.gain + self.release_step).min(target)
+        };
+
+        (l * self.gain, r * self.gain)
     }
 }
 
 pub struct SynthOutputConnection {
-    _stream: cpal::Stream,
+    _source: AudioSourceHandle,
     tx: std::sync::mpsc::Sender<MidiEvent>,
 }
 
@@ -165,4 +265,8 @@ impl OutputConnection for SynthOutputConnection {
         };
         self.tx.send(event).ok();
     }
+
+    fn metronome_event(&mut self, _midi_channel: Option<u8>, message: MidiMessage) {
+        self.midi_event(SYNTH_CLICK_CHANNEL, message);
+    }
 }
@@ -0,0 +1,84 @@
+use std::{collections::HashSet, hash::Hash, time::Duration};
+
+use crate::{EventLoopProxy, NeothesiaEvent};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// MIDI device plugged in or out while the app is running.
+#[derive(Debug, Clone)]
+pub enum PortEvent {
+    InputConnected(midi_io::MidiInputPort),
+    InputDisconnected(midi_io::MidiInputPort),
+    OutputConnected(midi_io::MidiOutputPort),
+    OutputDisconnected(midi_io::MidiOutputPort),
+}
+
+impl std::fmt::Display for PortEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PortEvent::InputConnected(port) => write!(f, "Input connected: {}", port),
+            PortEvent::InputDisconnected(port) => write!(f, "Input disconnected: {}", port),
+            PortEvent::OutputConnected(port) => write!(f, "Output connected: {}", port),
+            PortEvent::OutputDisconnected(port) => write!(f, "Output disconnected: {}", port),
+        }
+    }
+}
+
+/// Poll the MIDI ports on a background thread, changes are sent as `NeothesiaEvent::Port`.
+pub fn spawn(tx: EventLoopProxy) {
+    let res = std::thread::Builder::new()
+        .name("midi-port-watcher".into())
+        .spawn(move || {
+            let (input, output) = match (
+                midi_io::MidiInputManager::new(),
+                midi_io::MidiOutputManager::new(),
+            ) {
+                (Ok(input), Ok(output)) => (input, output),
+                (Err(err), _) | (_, Err(err)) => {
+                    log::error!("midi port watcher: {}", err);
+                    return;
+                }
+            };
+
+            let mut inputs: HashSet<_> = input.inputs().into_iter().collect();
+            let mut outputs: HashSet<_> = output.outputs().into_iter().collect();
+
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+
+                let new_inputs: HashSet<_> = input.inputs().into_iter().collect();
+                let new_outputs: HashSet<_> = output.outputs().into_iter().collect();
+
+                let (added, removed) = diff(&inputs, &new_inputs);
+                for port in removed {
+                    tx.send_event(NeothesiaEvent::Port(PortEvent::InputDisconnected(port)));
+                }
+                for port in added {
+                    tx.send_event(NeothesiaEvent::Port(PortEvent::InputConnected(port)));
+                }
+
+                let (added, removed) = diff(&outputs, &new_outputs);
+                for port in removed {
+                    tx.send_event(NeothesiaEvent::Port(PortEvent::OutputDisconnected(port)));
+                }
+                for port in added {
+                    tx.send_event(NeothesiaEvent::Port(PortEvent::OutputConnected(port)));
+                }
+
+                inputs = new_inputs;
+                outputs = new_outputs;
+            }
+        });
+
+    if let Err(err) = res {
+        log::error!("failed to start midi port watcher: {}", err);
+    }
+}
+
+/// `(added, removed)` ports.
+fn diff<T: Clone + Eq + Hash>(old: &HashSet<T>, new: &HashSet<T>) -> (Vec<T>, Vec<T>) {
+    (
+        new.difference(old).cloned().collect(),
+        old.difference(new).cloned().collect(),
+    )
+}
@@ -1,4 +1,4 @@
-use std::{path::PathBuf, rc::Rc};
+use std::{collections::HashMap, fmt, path::PathBuf, rc::Rc, time::Duration};
 
 use iced_graphics::{
     alignment::{Horizontal, Vertical},
@@ -13,8 +13,12 @@ use iced_native::{
 };
 
 use crate::{
-    config::PlayingSceneLayout,
-    output_manager::OutputDescriptor,
+    config::{
+        AudioConfig, BackingTrackConfig, InputFilterConfig, MetronomeConfig, MixerConfig,
+        PlayingSceneLayout, PracticeConfig, Subdivision, SynthEffectsConfig,
+    },
+    input_manager::VelocityCurve,
+    output_manager::{NoteMap, OutputDescriptor, OutputGroup},
     scene::menu_scene::neo_btn::neo_button,
     target::Target,
     ui::iced_state::{Element, Program},
@@ -25,12 +29,209 @@ mod theme;
 
 type InputDescriptor = midi_io::MidiInputPort;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputRoute {
+    SameAsOutput,
+    Output(OutputDescriptor),
+}
+
+impl fmt::Display for OutputRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputRoute::SameAsOutput => write!(f, "Same As Output"),
+            OutputRoute::Output(desc) => write!(f, "{}", desc),
+        }
+    }
+}
+
+/// Where the playback takes its tempo and position from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClockSource {
+    Internal,
+    Input(InputDescriptor),
+}
+
+impl fmt::Display for ClockSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClockSource::Internal => write!(f, "Internal"),
+            ClockSource::Input(port) => write!(f, "{}", port),
+        }
+    }
+}
+
+/// Output that receives the MIDI clock of the playback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClockOutput {
+    Off,
+    Output(OutputDescriptor),
+}
+
+impl fmt::Display for ClockOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClockOutput::Off => write!(f, "Off"),
+            ClockOutput::Output(desc) => write!(f, "{}", desc),
+        }
+    }
+}
+
+/// Audio setting that can be left to the system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AudioChoice<T> {
+    Default,
+    Custom(T),
+}
+
+impl<T> AudioChoice<T> {
+    fn new(value: Option<T>) -> Self {
+        value
+            .map(AudioChoice::Custom)
+            .unwrap_or(AudioChoice::Default)
+    }
+
+    fn value(self) -> Option<T> {
+        match self {
+            AudioChoice::Default => None,
+            AudioChoice::Custom(value) => Some(value),
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for AudioChoice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioChoice::Default => write!(f, "Default"),
+            AudioChoice::Custom(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Accented beats of a bar, shown as `X--X--`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccentPattern(Vec<bool>);
+
+impl AccentPattern {
+    fn parse(pattern: &str) -> Self {
+        Self(pattern.chars().map(|c| c == 'X').collect())
+    }
+}
+
+impl fmt::Display for AccentPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for accent in self.0.iter() {
+            write!(f, "{}", if *accent { 'X' } else { '-' })?;
+        }
+        Ok(())
+    }
+}
+
+const ACCENT_PATTERNS: [&str; 6] = ["X-", "X--", "X---", "X-X-", "X----", "X--X--"];
+
+/// Pulses of a practice bar split in accented groups, shown as `2+2+3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grouping(Vec<u32>);
+
+impl fmt::Display for Grouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, group) in self.0.iter().enumerate() {
+            if id > 0 {
+                write!(f, "+")?;
+            }
+            write!(f, "{}", group)?;
+        }
+        Ok(())
+    }
+}
+
+const GROUPINGS: [&[u32]; 9] = [
+    &[2],
+    &[3],
+    &[4],
+    &[5],
+    &[2, 2, 3],
+    &[3, 2, 2],
+    &[3, 3, 2],
+    &[2, 2, 2, 3],
+    &[3, 3, 3, 2],
+];
+
+/// Clicks of the second sound against the pulses of a practice bar, shown as `3:2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polyrhythm {
+    counter: u32,
+    pulses: u32,
+}
+
+impl fmt::Display for Polyrhythm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.counter == 0 {
+            write!(f, "Off")
+        } else {
+            write!(f, "{}:{}", self.counter, self.pulses)
+        }
+    }
+}
+
+/// Bars clicked before bars left silent by the practice metronome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GapTraining {
+    play: u32,
+    gap: u32,
+}
+
+impl fmt::Display for GapTraining {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.gap == 0 {
+            write!(f, "Off")
+        } else {
+            write!(f, "{} On / {} Off", self.play, self.gap)
+        }
+    }
+}
+
+const GAP_TRAININGS: [GapTraining; 6] = [
+    GapTraining { play: 1, gap: 0 },
+    GapTraining { play: 1, gap: 1 },
+    GapTraining { play: 2, gap: 1 },
+    GapTraining { play: 2, gap: 2 },
+    GapTraining { play: 3, gap: 1 },
+    GapTraining { play: 4, gap: 4 },
+];
+
+const BUFFER_SIZES: [u32; 7] = [32, 64, 128, 256, 512, 1024, 2048];
+const SAMPLE_RATES: [u32; 4] = [44100, 48000, 88200, 96000];
+
 #[derive(Debug, Clone)]
 pub enum Message {
     Tick,
+    /// A MIDI device was plugged in or out.
+    PortsChanged,
 
     SelectOutput(OutputDescriptor),
-    SelectInput(InputDescriptor),
+    SelectOutputRoute(OutputGroup, OutputRoute),
+    SelectNoteMap(NoteMap),
+    ToggleInput(InputDescriptor, bool),
+    SelectClockSource(ClockSource),
+    SelectClockOutput(ClockOutput),
+    #[cfg(target_os = "linux")]
+    VirtualPortsCheckbox(bool),
+    MinVelocitySlider(u8),
+    RetriggerMaskSlider(u32),
+    CrosstalkWindowSlider(u32),
+    SelectVelocityCurve(VelocityCurve),
+
+    SelectAudioHost(AudioChoice<String>),
+    SelectAudioDevice(AudioChoice<String>),
+    SelectBufferSize(AudioChoice<u32>),
+    SelectSampleRate(AudioChoice<u32>),
+    SynthEffectsChanged(SynthEffectsConfig),
+    MetronomeChanged(MetronomeConfig),
+    #[cfg(feature = "sample_kit")]
+    OpenMetronomeSamplePicker,
+    #[cfg(feature = "sample_kit")]
+    MetronomeSampleLoaded(Option<PathBuf>),
+    PracticeChanged(PracticeConfig),
 
     OpenMidiFilePicker,
     MidiFileLoaded(Option<lib_midi::Midi>),
@@ -38,15 +239,34 @@ pub enum Message {
     OpenSoundFontPicker,
     SoundFontFileLoaded(Option<PathBuf>),
 
+    #[cfg(feature = "sample_kit")]
+    OpenSampleKitPicker,
+    #[cfg(feature = "sample_kit")]
+    SampleKitFileLoaded(Option<PathBuf>),
+
+    OpenBackingTrackPicker,
+    BackingTrackFileLoaded(Option<PathBuf>),
+    ClearBackingTrack,
+    BackingTrackOffsetSlider(f32),
+    BackingTrackStretchSlider(f32),
+
+    MixerVolumeSlider(u8, u8),
+    MixerPanSlider(u8, i8),
+    MixerMuteCheckbox(u8, bool),
+    MixerSoloCheckbox(u8, bool),
+
     Play,
+    StartPractice,
 
     WaitForNotesCheckbox(bool),
     GuideNotesCheckbox(bool),
     MuteDrumsCheckbox(bool),
+    RecordSessionsCheckbox(bool),
 
     DrumsVolumeSlider(u8),
     MusicVolumeSlider(u8),
     MetronomeVolumeSlider(u8),
+    BackingTrackVolumeSlider(u8),
 
     SelectLayout(PlayingSceneLayout),
 
@@ -57,24 +277,63 @@ pub enum Message {
 struct Data {
     outputs: Vec<OutputDescriptor>,
     selected_output: Option<OutputDescriptor>,
+    routes: Vec<OutputRoute>,
+    selected_routes: HashMap<OutputGroup, OutputRoute>,
+    note_maps: Vec<NoteMap>,
+    selected_note_map: Option<NoteMap>,
     font_path: Option<PathBuf>,
+    #[cfg(feature = "sample_kit")]
+    kit_path: Option<PathBuf>,
     midi_file: Option<Rc<lib_midi::Midi>>,
+    backing_track: Option<BackingTrackConfig>,
+    /// Channels of the selected song, listed by the mixer.
+    mixer_channels: Vec<lib_midi::MidiChannel>,
+    mixer: MixerConfig,
 
     inputs: Vec<InputDescriptor>,
-    selected_input: Option<InputDescriptor>,
+    selected_inputs: Vec<InputDescriptor>,
+    /// Set once the first available input got a chance to be picked by default.
+    inputs_initialized: bool,
+    clock_sources: Vec<ClockSource>,
+    selected_clock_source: ClockSource,
+    clock_outputs: Vec<ClockOutput>,
+    selected_clock_output: ClockOutput,
+    #[cfg(target_os = "linux")]
+    virtual_ports: bool,
+    input_filter: InputFilterConfig,
+    velocity_curves: Vec<VelocityCurve>,
+    selected_velocity_curve: VelocityCurve,
+
+    audio_hosts: Vec<AudioChoice<String>>,
+    audio_devices: Vec<AudioChoice<String>>,
+    buffer_sizes: Vec<AudioChoice<u32>>,
+    sample_rates: Vec<AudioChoice<u32>>,
+    audio: AudioConfig,
+    /// Latency of the opened audio output.
+    audio_latency: Option<Duration>,
+    synth_effects: SynthEffectsConfig,
 
     wait_for_notes: bool,
     guide_notes: bool,
     mute_drums: bool,
+    record_sessions: bool,
     is_loading: bool,
 
     drums_volume: u8,
     music_volume: u8,
     metronome_volume: u8,
+    backing_track_volume: u8,
+    metronome: MetronomeConfig,
+    accent_patterns: Vec<AccentPattern>,
+    practice: PracticeConfig,
+    groupings: Vec<Grouping>,
 
     layouts: Vec<PlayingSceneLayout>,
     selected_layout: PlayingSceneLayout,
 
+    /// Subsystems that failed to initialize.
+    unavailable: Vec<&'static str>,
+
     logo_handle: ImageHandle,
 }
 
@@ -85,27 +344,86 @@ pub struct AppUi {
 
 impl AppUi {
     pub fn new(target: &mut Target) -> Self {
+        let note_maps = NoteMap::all();
+        let selected_note_map = note_maps
+            .iter()
+            .find(|map| Some(&map.name) == target.config.note_map.as_ref())
+            .or_else(|| note_maps.first())
+            .cloned();
+
+        // A finished practice is not a song to pick again
+        let midi_file = target
+            .midi_file
+            .clone()
+            .filter(|midi| midi.practice.is_none());
+
         Self {
             current: Step::Main,
             data: Data {
                 outputs: Vec::new(),
                 selected_output: None,
+                routes: Vec::new(),
+                selected_routes: HashMap::new(),
+                note_maps,
+                selected_note_map,
                 font_path: target.config.soundfont_path.clone(),
-                midi_file: target.midi_file.clone(),
+                #[cfg(feature = "sample_kit")]
+                kit_path: target.config.sample_kit_path.clone(),
+                backing_track: midi_file
+                    .as_ref()
+                    .and_then(|midi| target.config.backing_track(&midi.path))
+                    .cloned(),
+                mixer_channels: midi_file
+                    .as_ref()
+                    .map(|midi| midi.channels())
+                    .unwrap_or_default(),
+                mixer: midi_file
+                    .as_ref()
+                    .and_then(|midi| target.config.mixer(&midi.path))
+                    .cloned()
+                    .unwrap_or_default(),
+                midi_file,
 
                 inputs: Vec::new(),
-                selected_input: None,
+                selected_inputs: Vec::new(),
+                inputs_initialized: false,
+                clock_sources: vec![ClockSource::Internal],
+                selected_clock_source: ClockSource::Internal,
+                clock_outputs: vec![ClockOutput::Off],
+                selected_clock_output: ClockOutput::Off,
+                #[cfg(target_os = "linux")]
+                virtual_ports: target.config.virtual_ports,
+                input_filter: target.config.input_filter.clone(),
+                velocity_curves: VelocityCurve::PRESETS.to_vec(),
+                selected_velocity_curve: target.config.velocity_curve.clone(),
+
+                audio_hosts: audio_choices(crate::audio::hosts()),
+                audio_devices: audio_choices(crate::audio::devices(
+                    target.config.audio.host.as_deref(),
+                )),
+                buffer_sizes: audio_choices(BUFFER_SIZES),
+                sample_rates: audio_choices(SAMPLE_RATES),
+                audio: target.config.audio.clone(),
+                audio_latency: target.output_manager.borrow().audio_latency(),
+                synth_effects: target.config.synth_effects.clone(),
 
                 wait_for_notes: target.config.wait_for_notes,
                 guide_notes: target.config.guide_notes,
                 mute_drums: target.config.mute_drums,
+                record_sessions: target.config.record_sessions,
 
                 drums_volume: target.config.drums_volume,
                 music_volume: target.config.music_volume,
                 metronome_volume: target.config.metronome_volume,
+                backing_track_volume: target.config.backing_track_volume,
+                metronome: target.config.metronome.clone(),
+                accent_patterns: accent_patterns(&target.config.metronome.accents),
+                practice: target.config.practice.clone(),
+                groupings: groupings(&target.config.practice.groups),
 
                 layouts: vec![PlayingSceneLayout::Horizontal, PlayingSceneLayout::Vertical],
                 selected_layout: target.config.layout,
+                unavailable: Self::unavailable_subsystems(target),
                 is_loading: false,
 
                 logo_handle: ImageHandle::from_memory(include_bytes!("../img/banner.png").to_vec()),
@@ -114,6 +432,106 @@ impl AppUi {
     }
 }
 
+impl AppUi {
+    fn unavailable_subsystems(target: &Target) -> Vec<&'static str> {
+        let mut unavailable = Vec::new();
+
+        if !target.input_manager.is_available() {
+            unavailable.push("MIDI Input");
+        }
+
+        let output = target.output_manager.borrow();
+        if !output.is_midi_available() {
+            unavailable.push("MIDI Output");
+        }
+        #[cfg(feature = "synth")]
+        if !output.is_synth_available() {
+            unavailable.push("Audio");
+        }
+
+        unavailable
+    }
+
+    /// Fill in the files picked for outputs that need one.
+    fn with_paths(&self, out: OutputDescriptor) -> OutputDescriptor {
+        match out {
+            #[cfg(feature = "synth")]
+            OutputDescriptor::Synth(_) => OutputDescriptor::Synth(self.data.font_path.clone()),
+            #[cfg(feature = "sample_kit")]
+            OutputDescriptor::SampleKit(_) => {
+                OutputDescriptor::SampleKit(self.data.kit_path.clone())
+            }
+            o => o,
+        }
+    }
+
+    fn apply_audio_config(&mut self, target: &mut Target) {
+        let mut output = target.output_manager.borrow_mut();
+        output.set_audio_config(&target.config.audio);
+
+        self.data.audio = target.config.audio.clone();
+        self.data.audio_latency = output.audio_latency();
+    }
+
+    fn save_mixer(&self, target: &mut Target) {
+        if let Some(midi) = self.data.midi_file.as_ref() {
+            target.config.set_mixer(&midi.path, self.data.mixer.clone());
+        }
+    }
+
+    /// Connect the selected ports and start playing `midi`.
+    fn play(&self, target: &mut Target, midi: Rc<lib_midi::Midi>) {
+        target.midi_file = Some(midi);
+
+        target
+            .output_manager
+            .borrow_mut()
+            .set_metronome_sample(target.config.metronome.sample.as_deref());
+        target
+            .output_manager
+            .borrow_mut()
+            .set_metronome_midi_channel(target.config.metronome.midi_channel);
+
+        if let Some(out) = self.data.selected_output.clone() {
+            let out = self.with_paths(out);
+            target.output_manager.borrow_mut().connect(out);
+        }
+
+        target
+            .output_manager
+            .borrow_mut()
+            .set_note_map(self.data.selected_note_map.clone());
+
+        for group in OutputGroup::ALL {
+            let out = match self.data.selected_routes.get(&group) {
+                Some(OutputRoute::Output(out)) => Some(self.with_paths(out.clone())),
+                _ => None,
+            };
+            target.output_manager.borrow_mut().connect_route(group, out);
+        }
+
+        let clock = match &self.data.selected_clock_source {
+            ClockSource::Input(port) => Some(port),
+            ClockSource::Internal => None,
+        };
+        target
+            .input_manager
+            .connect_inputs(&self.data.selected_inputs, clock);
+
+        target
+            .proxy
+            .send_event(NeothesiaEvent::MainMenu(super::Event::Play));
+    }
+
+    fn save_backing_track(&self, target: &mut Target) {
+        if let Some(midi) = self.data.midi_file.as_ref() {
+            target
+                .config
+                .set_backing_track(&midi.path, self.data.backing_track.clone());
+        }
+    }
+}
+
 impl Program for AppUi {
     type Message = Message;
 
@@ -123,36 +541,26 @@ impl Program for AppUi {
                 self.current = page;
             }
             Message::Play => {
-                if self.data.midi_file.is_some() {
-                    target.midi_file = self.data.midi_file.take();
-
-                    if let Some(out) = self.data.selected_output.clone() {
-                        let out = match out {
-                            #[cfg(feature = "synth")]
-                            OutputDescriptor::Synth(_) => {
-                                OutputDescriptor::Synth(self.data.font_path.clone())
-                            }
-                            o => o,
-                        };
-
-                        target.output_manager.borrow_mut().connect(out);
-                    }
-
-                    if let Some(port) = self.data.selected_input.clone() {
-                        target.input_manager.connect_input(port);
-                    }
-
-                    target
-                        .proxy
-                        .send_event(NeothesiaEvent::MainMenu(super::Event::Play));
+                if let Some(mut midi) = self.data.midi_file.take() {
+                    // The metronome follows the settings without reloading the file
+                    target.midi_file = None;
+                    Rc::make_mut(&mut midi).set_metronome(&target.config.metronome.settings());
+                    self.play(target, midi);
                 }
             }
+            Message::StartPractice => match target.config.practice.settings().to_midi() {
+                Ok(midi) => self.play(target, Rc::new(midi)),
+                Err(err) => log::error!("{}", err),
+            },
             Message::OpenMidiFilePicker => {
                 self.data.is_loading = true;
                 return open_midi_file_picker(Message::MidiFileLoaded);
             }
             Message::MidiFileLoaded(midi) => {
                 if let Some(midi) = midi {
+                    self.data.backing_track = target.config.backing_track(&midi.path).cloned();
+                    self.data.mixer_channels = midi.channels();
+                    self.data.mixer = target.config.mixer(&midi.path).cloned().unwrap_or_default();
                     self.data.midi_file = Some(Rc::new(midi));
                 }
                 self.data.is_loading = false;
@@ -168,13 +576,165 @@ impl Program for AppUi {
                 }
                 self.data.is_loading = false;
             }
+            #[cfg(feature = "sample_kit")]
+            Message::OpenSampleKitPicker => {
+                self.data.is_loading = true;
+                return open_sample_kit_picker(Message::SampleKitFileLoaded);
+            }
+            #[cfg(feature = "sample_kit")]
+            Message::SampleKitFileLoaded(kit) => {
+                if let Some(kit) = kit {
+                    target.config.sample_kit_path = Some(kit.clone());
+                    self.data.kit_path = Some(kit);
+                }
+                self.data.is_loading = false;
+            }
+            Message::OpenBackingTrackPicker => {
+                self.data.is_loading = true;
+                return open_backing_track_picker(Message::BackingTrackFileLoaded);
+            }
+            Message::BackingTrackFileLoaded(path) => {
+                if let Some(path) = path {
+                    self.data.backing_track = Some(BackingTrackConfig::new(path));
+                    self.save_backing_track(target);
+                }
+                self.data.is_loading = false;
+            }
+            Message::ClearBackingTrack => {
+                self.data.backing_track = None;
+                self.save_backing_track(target);
+            }
+            Message::BackingTrackOffsetSlider(v) => {
+                if let Some(backing_track) = self.data.backing_track.as_mut() {
+                    backing_track.offset = v;
+                    self.save_backing_track(target);
+                }
+            }
+            Message::BackingTrackStretchSlider(v) => {
+                if let Some(backing_track) = self.data.backing_track.as_mut() {
+                    backing_track.time_stretch = v;
+                    self.save_backing_track(target);
+                }
+            }
+            Message::MixerVolumeSlider(channel, v) => {
+                self.data.mixer.channel_mut(channel).volume = v;
+                self.save_mixer(target);
+            }
+            Message::MixerPanSlider(channel, v) => {
+                self.data.mixer.channel_mut(channel).pan = v;
+                self.save_mixer(target);
+            }
+            Message::MixerMuteCheckbox(channel, v) => {
+                self.data.mixer.channel_mut(channel).mute = v;
+                self.save_mixer(target);
+            }
+            Message::MixerSoloCheckbox(channel, v) => {
+                self.data.mixer.channel_mut(channel).solo = v;
+                self.save_mixer(target);
+            }
             Message::SelectOutput(output) => {
                 target.config.set_output(&output);
                 self.data.selected_output = Some(output);
             }
-            Message::SelectInput(input) => {
-                target.config.set_input(Some(&input));
-                self.data.selected_input = Some(input);
+            Message::SelectOutputRoute(group, route) => {
+                match &route {
+                    OutputRoute::SameAsOutput => target.config.set_output_route(group, None),
+                    OutputRoute::Output(out) => target.config.set_output_route(group, Some(out)),
+                }
+                self.data.selected_routes.insert(group, route);
+            }
+            Message::SelectNoteMap(note_map) => {
+                target.config.note_map = Some(note_map.name.clone());
+                self.data.selected_note_map = Some(note_map);
+            }
+            Message::ToggleInput(input, enabled) => {
+                target.config.set_input_enabled(&input, enabled);
+            }
+            Message::SelectClockSource(source) => {
+                target.config.clock_input = match &source {
+                    ClockSource::Input(port) => Some(port.to_string()),
+                    ClockSource::Internal => None,
+                };
+                self.data.selected_clock_source = source;
+            }
+            Message::SelectClockOutput(output) => {
+                target.config.clock_output = match &output {
+                    ClockOutput::Output(desc) => Some(desc.to_string()),
+                    ClockOutput::Off => None,
+                };
+                self.data.selected_clock_output = output;
+            }
+            #[cfg(target_os = "linux")]
+            Message::VirtualPortsCheckbox(v) => {
+                target.config.virtual_ports = v;
+                target.update_virtual_ports();
+                self.data.virtual_ports = v;
+            }
+            Message::SelectAudioHost(host) => {
+                target.config.audio.host = host.value();
+                target.config.audio.device = None;
+                self.data.audio_devices =
+                    audio_choices(crate::audio::devices(target.config.audio.host.as_deref()));
+                self.apply_audio_config(target);
+            }
+            Message::SelectAudioDevice(device) => {
+                target.config.audio.device = device.value();
+                self.apply_audio_config(target);
+            }
+            Message::SelectBufferSize(size) => {
+                target.config.audio.buffer_size = size.value();
+                self.apply_audio_config(target);
+            }
+            Message::SelectSampleRate(rate) => {
+                target.config.audio.sample_rate = rate.value();
+                self.apply_audio_config(target);
+            }
+            Message::SynthEffectsChanged(effects) => {
+                target
+                    .output_manager
+                    .borrow_mut()
+                    .set_synth_effects(&effects);
+                target.config.synth_effects = effects.clone();
+                self.data.synth_effects = effects;
+            }
+            Message::MetronomeChanged(metronome) => {
+                self.data.accent_patterns = accent_patterns(&metronome.accents);
+                target.config.metronome = metronome.clone();
+                self.data.metronome = metronome;
+            }
+            #[cfg(feature = "sample_kit")]
+            Message::OpenMetronomeSamplePicker => {
+                self.data.is_loading = true;
+                return open_metronome_sample_picker(Message::MetronomeSampleLoaded);
+            }
+            #[cfg(feature = "sample_kit")]
+            Message::MetronomeSampleLoaded(sample) => {
+                if let Some(sample) = sample {
+                    target.config.metronome.sample = Some(sample.clone());
+                    self.data.metronome.sample = Some(sample);
+                }
+                self.data.is_loading = false;
+            }
+            Message::PracticeChanged(practice) => {
+                self.data.groupings = groupings(&practice.groups);
+                target.config.practice = practice.clone();
+                self.data.practice = practice;
+            }
+            Message::MinVelocitySlider(v) => {
+                target.config.input_filter.min_velocity = v;
+                self.data.input_filter.min_velocity = v;
+            }
+            Message::RetriggerMaskSlider(v) => {
+                target.config.input_filter.retrigger_mask_ms = v;
+                self.data.input_filter.retrigger_mask_ms = v;
+            }
+            Message::CrosstalkWindowSlider(v) => {
+                target.config.input_filter.crosstalk_window_ms = v;
+                self.data.input_filter.crosstalk_window_ms = v;
+            }
+            Message::SelectVelocityCurve(v) => {
+                target.config.velocity_curve = v.clone();
+                self.data.selected_velocity_curve = v;
             }
             Message::WaitForNotesCheckbox(v) => {
                 target.config.wait_for_notes = v;
@@ -188,6 +748,10 @@ impl Program for AppUi {
                 target.config.mute_drums = v;
                 self.data.mute_drums = v;
             }
+            Message::RecordSessionsCheckbox(v) => {
+                target.config.record_sessions = v;
+                self.data.record_sessions = v;
+            }
             Message::DrumsVolumeSlider(v) => {
                 target.config.drums_volume = v;
                 self.data.drums_volume = v;
@@ -200,14 +764,49 @@ impl Program for AppUi {
                 target.config.metronome_volume = v;
                 self.data.metronome_volume = v;
             }
+            Message::BackingTrackVolumeSlider(v) => {
+                target.config.backing_track_volume = v;
+                self.data.backing_track_volume = v;
+            }
             Message::SelectLayout(v) => {
                 target.config.layout = v;
                 self.data.selected_layout = v;
             }
+            Message::PortsChanged => {
+                // Port ids shifted, pick the configured ports again on the next tick
+                self.data.selected_output = None;
+                self.data.selected_routes.clear();
+            }
             Message::Tick => {
                 self.data.outputs = target.output_manager.borrow().outputs();
                 self.data.inputs = target.input_manager.inputs();
 
+                self.data.routes = std::iter::once(OutputRoute::SameAsOutput)
+                    .chain(self.data.outputs.iter().cloned().map(OutputRoute::Output))
+                    .collect();
+
+                for group in OutputGroup::ALL {
+                    if self.data.selected_routes.contains_key(&group) {
+                        continue;
+                    }
+
+                    let route = target
+                        .config
+                        .output_routes
+                        .get(&group)
+                        .and_then(|name| {
+                            self.data
+                                .outputs
+                                .iter()
+                                .find(|output| &output.to_string() == name)
+                        })
+                        .cloned()
+                        .map(OutputRoute::Output)
+                        .unwrap_or(OutputRoute::SameAsOutput);
+
+                    self.data.selected_routes.insert(group, route);
+                }
+
                 if self.data.selected_output.is_none() {
                     if let Some(out) = self
                         .data
@@ -221,18 +820,62 @@ impl Program for AppUi {
                     }
                 }
 
-                if self.data.selected_input.is_none() {
-                    if let Some(input) = self
-                        .data
-                        .inputs
-                        .iter()
-                        .find(|input| Some(input.to_string()) == target.config.input)
-                    {
-                        self.data.selected_input = Some(input.clone());
-                    } else {
-                        self.data.selected_input = self.data.inputs.first().cloned();
+                if target.config.inputs.is_empty() && !self.data.inputs_initialized {
+                    if let Some(input) = self.data.inputs.first() {
+                        target.config.set_input_enabled(input, true);
                     }
                 }
+                self.data.inputs_initialized |= !self.data.inputs.is_empty();
+
+                self.data.selected_inputs = self
+                    .data
+                    .inputs
+                    .iter()
+                    .filter(|input| target.config.inputs.contains(&input.to_string()))
+                    .cloned()
+                    .collect();
+
+                self.data.clock_sources = std::iter::once(ClockSource::Internal)
+                    .chain(self.data.inputs.iter().cloned().map(ClockSource::Input))
+                    .collect();
+
+                self.data.selected_clock_source = target
+                    .config
+                    .clock_input
+                    .as_ref()
+                    .and_then(|name| {
+                        self.data
+                            .inputs
+                            .iter()
+                            .find(|input| &input.to_string() == name)
+                    })
+                    .cloned()
+                    .map(ClockSource::Input)
+                    .unwrap_or(ClockSource::Internal);
+
+                self.data.clock_outputs = std::iter::once(ClockOutput::Off)
+                    .chain(
+                        self.data
+                            .outputs
+                            .iter()
+                            .filter(|output| matches!(output, OutputDescriptor::MidiOut(_)))
+                            .cloned()
+                            .map(ClockOutput::Output),
+                    )
+                    .collect();
+
+                self.data.selected_clock_output = target
+                    .config
+                    .clock_output
+                    .as_ref()
+                    .and_then(|name| {
+                        self.data
+                            .clock_outputs
+                            .iter()
+                            .find(|output| &output.to_string() == name)
+                    })
+                    .cloned()
+                    .unwrap_or(ClockOutput::Off);
             }
             Message::ExitApp => {
                 target.proxy.send_event(NeothesiaEvent::GoBack);
@@ -256,6 +899,12 @@ impl Program for AppUi {
                     Step::Main => Some(Message::GoToPage(Step::Settings)),
                     _ => None,
                 },
+                KeyCode::M => match self.current {
+                    Step::Main if self.data.midi_file.is_some() => {
+                        Some(Message::GoToPage(Step::Mixer))
+                    }
+                    _ => None,
+                },
                 KeyCode::A => match self.current {
                     Step::Main => Some(Message::WaitForNotesCheckbox(!self.data.guide_notes)),
                     _ => None,
@@ -267,12 +916,15 @@ impl Program for AppUi {
                 KeyCode::Enter => match self.current {
                     Step::Exit => Some(Message::ExitApp),
                     Step::Main => Some(Message::Play),
+                    Step::Practice => Some(Message::StartPractice),
                     _ => None,
                 },
                 KeyCode::Escape => Some(match self.current {
                     Step::Exit => Message::GoToPage(Step::Main),
                     Step::Main => Message::GoToPage(Step::Exit),
                     Step::Settings => Message::GoToPage(Step::Main),
+                    Step::Mixer => Message::GoToPage(Step::Main),
+                    Step::Practice => Message::GoToPage(Step::Main),
                 }),
                 _ => None,
             }
@@ -291,6 +943,8 @@ pub enum Step {
     Exit,
     Main,
     Settings,
+    Mixer,
+    Practice,
 }
 
 impl<'a> Step {
@@ -303,6 +957,8 @@ impl<'a> Step {
             Self::Exit => Self::exit(),
             Self::Main => Self::main(data),
             Self::Settings => Self::settings(data),
+            Self::Mixer => Self::mixer(data),
+            Self::Practice => Self::practice(data),
         }
     }
 
@@ -342,6 +998,10 @@ impl<'a> Step {
                 .on_press(Message::OpenMidiFilePicker)
                 .width(Length::Fill)
                 .height(Length::Units(80)),
+            neo_button("Practice")
+                .on_press(Message::GoToPage(Step::Practice))
+                .width(Length::Fill)
+                .height(Length::Units(80)),
             neo_button("Settings")
                 .on_press(Message::GoToPage(Step::Settings))
                 .width(Length::Fill)
@@ -354,10 +1014,14 @@ impl<'a> Step {
         .width(Length::Units(450))
         .spacing(10);
 
-        let column = col![image(data.logo_handle.clone()), buttons]
+        let mut column = col![image(data.logo_handle.clone()), buttons]
             .spacing(40)
             .align_items(Alignment::Center);
 
+        if let Some(notice) = Self::unavailable_notice(data) {
+            column = column.push(notice);
+        }
+
         let mut content = top_padded(column);
 
         if data.midi_file.is_some() {
@@ -375,12 +1039,24 @@ impl<'a> Step {
             )
             .style(theme::checkbox());
 
+            let mixer = neo_button("Mixer")
+                .height(Length::Units(60))
+                .min_width(80)
+                .on_press(Message::GoToPage(Step::Mixer));
+
             let play = neo_button("Play")
                 .height(Length::Units(60))
                 .min_width(80)
                 .on_press(Message::Play);
 
-            let row = row![guide_notes, mute_drums, wait_for_notes, play]
+            let record = checkbox(
+                "Record",
+                data.record_sessions,
+                Message::RecordSessionsCheckbox,
+            )
+            .style(theme::checkbox());
+
+            let row = row![guide_notes, mute_drums, wait_for_notes, record, mixer, play]
                 .spacing(20)
                 .align_items(Alignment::Center);
 
@@ -404,7 +1080,13 @@ impl<'a> Step {
         let outputs = &data.outputs;
         let selected_output = data.selected_output.clone();
 
-        let is_synth = matches!(selected_output, Some(OutputDescriptor::Synth(_)));
+        // Outputs that play files get a button to pick one
+        let file_picker = match &selected_output {
+            Some(OutputDescriptor::Synth(_)) => Some(("SoundFont", Message::OpenSoundFontPicker)),
+            #[cfg(feature = "sample_kit")]
+            Some(OutputDescriptor::SampleKit(_)) => Some(("Kit", Message::OpenSampleKitPicker)),
+            _ => None,
+        };
 
         let output_list = pick_list(outputs, selected_output, Message::SelectOutput)
             .width(Length::Fill)
@@ -414,10 +1096,10 @@ impl<'a> Step {
             .vertical_alignment(Vertical::Center)
             .height(Length::Units(30));
 
-        let output_list = if is_synth {
-            let btn = button(centered_text("SoundFont"))
+        let output_list = if let Some((label, message)) = file_picker {
+            let btn = button(centered_text(label))
                 .width(Length::Units(50))
-                .on_press(Message::OpenSoundFontPicker)
+                .on_press(message)
                 .style(theme::button());
 
             row![
@@ -430,20 +1112,123 @@ impl<'a> Step {
         }
         .spacing(10);
 
-        let inputs = &data.inputs;
-        let selected_input = data.selected_input.clone();
-
-        let input_list = pick_list(inputs, selected_input, Message::SelectInput)
+        let mut routes_list = widget::Column::new().spacing(10);
+        for group in OutputGroup::ALL {
+            let route_list = pick_list(
+                &data.routes,
+                data.selected_routes.get(&group).cloned(),
+                move |route| Message::SelectOutputRoute(group, route),
+            )
             .width(Length::Fill)
             .style(theme::pick_list());
 
-        let input_title = text("Input:")
+            let route_title = text(format!("{} Out:", group))
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Units(30));
+
+            routes_list = routes_list.push(
+                row![
+                    route_title.width(Length::Units(120)),
+                    route_list.width(Length::FillPortion(3)),
+                ]
+                .spacing(10),
+            );
+        }
+
+        let note_map_list = pick_list(
+            &data.note_maps,
+            data.selected_note_map.clone(),
+            Message::SelectNoteMap,
+        )
+        .width(Length::Fill)
+        .style(theme::pick_list());
+
+        let note_map_title = text("Note Map:")
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let note_map_list = row![
+            note_map_title.width(Length::Units(120)),
+            note_map_list.width(Length::FillPortion(3)),
+        ]
+        .spacing(10);
+
+        let mut inputs = widget::Column::new().spacing(5);
+        for input in data.inputs.iter() {
+            let is_selected = data.selected_inputs.contains(input);
+            let port = input.clone();
+
+            inputs = inputs.push(
+                checkbox(input.to_string(), is_selected, move |v| {
+                    Message::ToggleInput(port.clone(), v)
+                })
+                .style(theme::checkbox()),
+            );
+        }
+
+        if data.inputs.is_empty() {
+            inputs = inputs.push(
+                text("No MIDI inputs")
+                    .vertical_alignment(Vertical::Center)
+                    .height(Length::Units(30)),
+            );
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            inputs = inputs.push(
+                checkbox(
+                    "Virtual Ports (Drumsthesia In/Out)",
+                    data.virtual_ports,
+                    Message::VirtualPortsCheckbox,
+                )
+                .style(theme::checkbox()),
+            );
+        }
+
+        let input_title = text("Inputs:")
             .vertical_alignment(Vertical::Center)
             .height(Length::Units(30));
 
         let input_list = row![
             input_title.width(Length::Units(60)),
-            input_list.width(Length::FillPortion(3)),
+            inputs.width(Length::FillPortion(3)),
+        ]
+        .spacing(10);
+
+        let clock_list = pick_list(
+            &data.clock_sources,
+            Some(data.selected_clock_source.clone()),
+            Message::SelectClockSource,
+        )
+        .width(Length::Fill)
+        .style(theme::pick_list());
+
+        let clock_title = text("Clock:")
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let clock_list = row![
+            clock_title.width(Length::Units(60)),
+            clock_list.width(Length::FillPortion(3)),
+        ]
+        .spacing(10);
+
+        let clock_output_list = pick_list(
+            &data.clock_outputs,
+            Some(data.selected_clock_output.clone()),
+            Message::SelectClockOutput,
+        )
+        .width(Length::Fill)
+        .style(theme::pick_list());
+
+        let clock_output_title = text("Clock Out:")
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let clock_output_list = row![
+            clock_output_title.width(Length::Units(60)),
+            clock_output_list.width(Length::FillPortion(3)),
         ]
         .spacing(10);
 
@@ -506,30 +1291,863 @@ impl<'a> Step {
         ]
         .spacing(10);
 
+        let backing_track_volume_title = text("Backing Track:")
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let backing_track_volume = slider(
+            0..=127,
+            data.backing_track_volume,
+            Message::BackingTrackVolumeSlider,
+        )
+        .width(Length::Fill)
+        .style(theme::slider());
+
+        let backing_track_volume_list = row![
+            backing_track_volume_title.width(Length::Units(120)),
+            backing_track_volume.width(Length::FillPortion(3))
+        ]
+        .spacing(10);
+
+        let mut settings = col![
+            output_list,
+            routes_list,
+            note_map_list,
+            Self::audio_settings(data),
+            input_list,
+            clock_list,
+            clock_output_list,
+            Self::input_filter_settings(data),
+            layout_list,
+            drums_volume_list,
+            music_volume_list,
+            metronome_volume_list,
+            Self::metronome_settings(data),
+            backing_track_volume_list,
+        ]
+        .spacing(10);
+
+        if let Some(OutputDescriptor::Synth(_)) = data.selected_output {
+            settings = settings.push(Self::synth_effects_settings(data));
+        }
+
+        if data.midi_file.is_some() {
+            settings = settings.push(Self::backing_track_settings(data));
+        }
+
         let buttons = row![neo_button("Back")
             .on_press(Message::GoToPage(Step::Main))
             .width(Length::Fill),]
         .width(Length::Shrink)
         .height(Length::Units(50));
 
-        let column = col![
-            image(data.logo_handle.clone()),
-            col![
-                output_list,
-                input_list,
-                layout_list,
-                drums_volume_list,
-                music_volume_list,
-                metronome_volume_list,
+        let mut column = col![image(data.logo_handle.clone())]
+            .spacing(40)
+            .align_items(Alignment::Center);
+
+        if let Some(notice) = Self::unavailable_notice(data) {
+            column = column.push(notice);
+        }
+
+        let column = column.push(settings).push(buttons);
+
+        center_x(top_padded(column)).into()
+    }
+
+    fn mixer(data: &'a Data) -> Element<'a, Message> {
+        let title = text("Mixer")
+            .size(40)
+            .horizontal_alignment(Horizontal::Center)
+            .width(Length::Fill);
+
+        let header = row![
+            text("Channel").width(Length::Units(200)),
+            text("Volume").width(Length::FillPortion(2)),
+            text("Pan").width(Length::FillPortion(1)),
+            text("Mute").width(Length::Units(60)),
+            text("Solo").width(Length::Units(60)),
+        ]
+        .spacing(10);
+
+        let mut channels = col![header].spacing(5);
+
+        for midi_channel in data.mixer_channels.iter() {
+            let channel = midi_channel.channel;
+            let mix = data.mixer.channel(channel).cloned().unwrap_or_default();
+
+            let name = text(format!(
+                "{}: {}",
+                channel + 1,
+                midi_channel.instrument_name()
+            ))
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+            let volume = slider(0..=127, mix.volume, move |v| {
+                Message::MixerVolumeSlider(channel, v)
+            })
+            .width(Length::Fill)
+            .style(theme::slider());
+
+            let pan = slider(-64..=63, mix.pan as i32, move |v| {
+                Message::MixerPanSlider(channel, v as i8)
+            })
+            .width(Length::Fill)
+            .style(theme::slider());
+
+            let mute = checkbox("", mix.mute, move |v| {
+                Message::MixerMuteCheckbox(channel, v)
+            })
+            .style(theme::checkbox());
+
+            let solo = checkbox("", mix.solo, move |v| {
+                Message::MixerSoloCheckbox(channel, v)
+            })
+            .style(theme::checkbox());
+
+            let row = row![
+                name.width(Length::Units(200)),
+                volume.width(Length::FillPortion(2)),
+                pan.width(Length::FillPortion(1)),
+                container(mute).width(Length::Units(60)),
+                container(solo).width(Length::Units(60)),
             ]
-            .spacing(10),
-            buttons
+            .spacing(10)
+            .align_items(Alignment::Center);
+
+            channels = channels.push(row);
+        }
+
+        let buttons = row![neo_button("Back")
+            .on_press(Message::GoToPage(Step::Main))
+            .width(Length::Fill),]
+        .width(Length::Shrink)
+        .height(Length::Units(50));
+
+        let column = col![title, channels, buttons]
+            .spacing(30)
+            .align_items(Alignment::Center);
+
+        center_x(top_padded(column)).into()
+    }
+
+    fn unavailable_notice(data: &'a Data) -> Option<Element<'a, Message>> {
+        if data.unavailable.is_empty() {
+            return None;
+        }
+
+        let notice = text(format!("Unavailable: {}", data.unavailable.join(", ")))
+            .horizontal_alignment(Horizontal::Center)
+            .width(Length::Fill);
+
+        Some(notice.into())
+    }
+
+    fn audio_settings(data: &'a Data) -> Element<'a, Message> {
+        let audio = &data.audio;
+
+        let host_title = text("Audio Host:")
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let host = pick_list(
+            &data.audio_hosts,
+            Some(AudioChoice::new(audio.host.clone())),
+            Message::SelectAudioHost,
+        )
+        .width(Length::Fill)
+        .style(theme::pick_list());
+
+        let host = row![
+            host_title.width(Length::Units(120)),
+            host.width(Length::FillPortion(3))
+        ]
+        .spacing(10);
+
+        let device_title = text("Audio Device:")
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let device = pick_list(
+            &data.audio_devices,
+            Some(AudioChoice::new(audio.device.clone())),
+            Message::SelectAudioDevice,
+        )
+        .width(Length::Fill)
+        .style(theme::pick_list());
+
+        let device = row![
+            device_title.width(Length::Units(120)),
+            device.width(Length::FillPortion(3))
         ]
-        .spacing(40)
-        .align_items(Alignment::Center);
+        .spacing(10);
+
+        let buffer_size = pick_list(
+            &data.buffer_sizes,
+            Some(AudioChoice::new(audio.buffer_size)),
+            Message::SelectBufferSize,
+        )
+        .width(Length::Fill)
+        .style(theme::pick_list());
+
+        let sample_rate = pick_list(
+            &data.sample_rates,
+            Some(AudioChoice::new(audio.sample_rate)),
+            Message::SelectSampleRate,
+        )
+        .width(Length::Fill)
+        .style(theme::pick_list());
+
+        let latency = match data.audio_latency {
+            Some(latency) => format!("Latency: {:.1}ms", latency.as_secs_f64() * 1000.0),
+            None => "Latency: unknown".to_string(),
+        };
+        let latency = text(latency)
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let buffer = row![
+            text("Buffer / Rate:")
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Units(30))
+                .width(Length::Units(120)),
+            buffer_size.width(Length::FillPortion(1)),
+            sample_rate.width(Length::FillPortion(1)),
+            latency.width(Length::FillPortion(1)),
+        ]
+        .spacing(10);
+
+        col![host, device, buffer].spacing(10).into()
+    }
+
+    fn synth_effects_settings(data: &'a Data) -> Element<'a, Message> {
+        let effects = &data.synth_effects;
+
+        let labeled = |title: String, widget: Element<'a, Message>| {
+            let title = text(title)
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Units(30));
+
+            row![title.width(Length::Units(120)), widget].spacing(10)
+        };
+
+        let gain = {
+            let e = effects.clone();
+            slider(0.0..=1.0, effects.master_gain, move |master_gain| {
+                Message::SynthEffectsChanged(SynthEffectsConfig {
+                    master_gain,
+                    ..e.clone()
+                })
+            })
+            .step(0.01)
+            .width(Length::FillPortion(3))
+            .style(theme::slider())
+        };
+
+        let limiter = {
+            let e = effects.clone();
+            checkbox("Limiter", effects.limiter, move |limiter| {
+                Message::SynthEffectsChanged(SynthEffectsConfig {
+                    limiter,
+                    ..e.clone()
+                })
+            })
+            .style(theme::checkbox())
+        };
+
+        let gain = labeled(
+            format!("Gain: {:.2}", effects.master_gain),
+            row![gain, limiter]
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .into(),
+        );
+
+        let room_size = {
+            let e = effects.clone();
+            slider(
+                0.0..=1.0,
+                effects.reverb_room_size,
+                move |reverb_room_size| {
+                    Message::SynthEffectsChanged(SynthEffectsConfig {
+                        reverb_room_size,
+                        ..e.clone()
+                    })
+                },
+            )
+            .step(0.01)
+            .style(theme::slider())
+        };
+        let room_size = labeled(
+            format!("Room Size: {:.2}", effects.reverb_room_size),
+            room_size.into(),
+        );
+
+        let damping = {
+            let e = effects.clone();
+            slider(0.0..=1.0, effects.reverb_damping, move |reverb_damping| {
+                Message::SynthEffectsChanged(SynthEffectsConfig {
+                    reverb_damping,
+                    ..e.clone()
+                })
+            })
+            .step(0.01)
+            .style(theme::slider())
+        };
+        let damping = labeled(
+            format!("Damping: {:.2}", effects.reverb_damping),
+            damping.into(),
+        );
+
+        let level = {
+            let e = effects.clone();
+            slider(0.0..=1.0, effects.reverb_level, move |reverb_level| {
+                Message::SynthEffectsChanged(SynthEffectsConfig {
+                    reverb_level,
+                    ..e.clone()
+                })
+            })
+            .step(0.01)
+            .style(theme::slider())
+        };
+        let level = labeled(format!("Reverb: {:.2}", effects.reverb_level), level.into());
+
+        let drums_reverb = {
+            let e = effects.clone();
+            slider(0..=127, effects.drums_reverb, move |drums_reverb| {
+                Message::SynthEffectsChanged(SynthEffectsConfig {
+                    drums_reverb,
+                    ..e.clone()
+                })
+            })
+            .style(theme::slider())
+        };
+        let drums_reverb = labeled(
+            format!("Drums Rev: {}", effects.drums_reverb),
+            drums_reverb.into(),
+        );
+
+        let music_reverb = {
+            let e = effects.clone();
+            slider(0..=127, effects.music_reverb, move |music_reverb| {
+                Message::SynthEffectsChanged(SynthEffectsConfig {
+                    music_reverb,
+                    ..e.clone()
+                })
+            })
+            .style(theme::slider())
+        };
+        let music_reverb = labeled(
+            format!("Music Rev: {}", effects.music_reverb),
+            music_reverb.into(),
+        );
+
+        let chorus = {
+            let e = effects.clone();
+            slider(0..=127, effects.chorus, move |chorus| {
+                Message::SynthEffectsChanged(SynthEffectsConfig {
+                    chorus,
+                    ..e.clone()
+                })
+            })
+            .style(theme::slider())
+        };
+        let chorus = labeled(format!("Chorus: {}", effects.chorus), chorus.into());
+
+        col![
+            gain,
+            room_size,
+            damping,
+            level,
+            drums_reverb,
+            music_reverb,
+            chorus
+        ]
+        .spacing(10)
+        .into()
+    }
+
+    fn practice(data: &'a Data) -> Element<'a, Message> {
+        let practice = &data.practice;
+
+        let title = text("Practice Metronome")
+            .size(40)
+            .horizontal_alignment(Horizontal::Center)
+            .width(Length::Fill);
+
+        let labeled = |title: String, widget: Element<'a, Message>| {
+            let title = text(title)
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Units(30));
+
+            row![title.width(Length::Units(200)), widget].spacing(10)
+        };
+
+        let start_bpm = {
+            let p = practice.clone();
+            slider(30.0..=240.0, practice.start_bpm, move |start_bpm| {
+                Message::PracticeChanged(PracticeConfig {
+                    start_bpm,
+                    ..p.clone()
+                })
+            })
+            .step(1.0)
+            .style(theme::slider())
+        };
+
+        let end_bpm = {
+            let p = practice.clone();
+            slider(30.0..=240.0, practice.end_bpm, move |end_bpm| {
+                Message::PracticeChanged(PracticeConfig {
+                    end_bpm,
+                    ..p.clone()
+                })
+            })
+            .step(1.0)
+            .style(theme::slider())
+        };
+
+        let tempo = labeled(
+            format!(
+                "Tempo: {:.0} - {:.0} BPM",
+                practice.start_bpm, practice.end_bpm
+            ),
+            row![start_bpm, end_bpm].spacing(10).into(),
+        );
+
+        let bars = {
+            let p = practice.clone();
+            slider(1..=64, practice.bars, move |bars| {
+                Message::PracticeChanged(PracticeConfig { bars, ..p.clone() })
+            })
+            .style(theme::slider())
+        };
+        let bars = labeled(format!("Bars: {}", practice.bars), bars.into());
+
+        let grouping = {
+            let p = practice.clone();
+            pick_list(
+                &data.groupings,
+                Some(Grouping(practice.groups.clone())),
+                move |Grouping(groups)| {
+                    Message::PracticeChanged(PracticeConfig {
+                        groups,
+                        ..p.clone()
+                    })
+                },
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list())
+        };
+        let grouping = labeled("Grouping:".into(), grouping.into());
+
+        let pulses = practice.groups.iter().sum::<u32>().max(1);
+        let polyrhythm = {
+            let p = practice.clone();
+            pick_list(
+                polyrhythms(practice.counter, pulses),
+                Some(Polyrhythm {
+                    counter: practice.counter,
+                    pulses,
+                }),
+                move |Polyrhythm { counter, .. }| {
+                    Message::PracticeChanged(PracticeConfig {
+                        counter,
+                        ..p.clone()
+                    })
+                },
+            )
+            .width(Length::Fill)
+            .style(theme::pick_list())
+        };
+        let polyrhythm = labeled("Polyrhythm:".into(), polyrhythm.into());
+
+        let gap = {
+            let p = practice.clone();
+            let selected = GapTraining {
+                play: practice.play_bars,
+                gap: practice.gap_bars,
+            };
+            let mut options = GAP_TRAININGS.to_vec();
+            if !options.contains(&selected) {
+                options.push(selected);
+            }
+
+            pick_list(options, Some(selected), move |GapTraining { play, gap }| {
+                Message::PracticeChanged(PracticeConfig {
+                    play_bars: play,
+                    gap_bars: gap,
+                    ..p.clone()
+                })
+            })
+            .width(Length::Fill)
+            .style(theme::pick_list())
+        };
+        let gap = labeled("Mute Bars:".into(), gap.into());
+
+        let settings = col![tempo, bars, grouping, polyrhythm, gap]
+            .spacing(10)
+            .width(Length::Units(650));
+
+        let buttons = row![
+            neo_button("Back")
+                .on_press(Message::GoToPage(Step::Main))
+                .width(Length::Fill),
+            neo_button("Start")
+                .on_press(Message::StartPractice)
+                .width(Length::Fill),
+        ]
+        .spacing(10)
+        .width(Length::Units(450))
+        .height(Length::Units(50));
+
+        let column = col![image(data.logo_handle.clone()), title, settings, buttons]
+            .spacing(30)
+            .align_items(Alignment::Center);
 
         center_x(top_padded(column)).into()
     }
+
+    fn metronome_settings(data: &'a Data) -> Element<'a, Message> {
+        let metronome = &data.metronome;
+
+        let labeled = |title: String, widget: Element<'a, Message>| {
+            let title = text(title)
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Units(30));
+
+            row![title.width(Length::Units(120)), widget].spacing(10)
+        };
+
+        let subdivision = {
+            let m = metronome.clone();
+            pick_list(
+                Subdivision::ALL.to_vec(),
+                Some(metronome.subdivision),
+                move |subdivision| {
+                    Message::MetronomeChanged(MetronomeConfig {
+                        subdivision,
+                        ..m.clone()
+                    })
+                },
+            )
+            .width(Length::FillPortion(1))
+            .style(theme::pick_list())
+        };
+
+        let accents = {
+            let m = metronome.clone();
+            pick_list(
+                &data.accent_patterns,
+                Some(AccentPattern(metronome.accents.clone())),
+                move |AccentPattern(accents)| {
+                    Message::MetronomeChanged(MetronomeConfig {
+                        accents,
+                        ..m.clone()
+                    })
+                },
+            )
+            .width(Length::FillPortion(1))
+            .style(theme::pick_list())
+        };
+
+        let pattern = labeled(
+            "Click / Accents:".into(),
+            row![subdivision, accents].spacing(10).into(),
+        );
+
+        let program = {
+            let m = metronome.clone();
+            slider(0..=127, metronome.program, move |program| {
+                Message::MetronomeChanged(MetronomeConfig {
+                    program,
+                    ..m.clone()
+                })
+            })
+            .style(theme::slider())
+        };
+        let program = labeled(
+            format!("Sound: {}", lib_midi::program_name(metronome.program)),
+            program.into(),
+        );
+
+        let accent_key = {
+            let m = metronome.clone();
+            slider(0..=127, metronome.accent_key, move |accent_key| {
+                Message::MetronomeChanged(MetronomeConfig {
+                    accent_key,
+                    ..m.clone()
+                })
+            })
+            .style(theme::slider())
+        };
+
+        let regular_key = {
+            let m = metronome.clone();
+            slider(0..=127, metronome.regular_key, move |regular_key| {
+                Message::MetronomeChanged(MetronomeConfig {
+                    regular_key,
+                    ..m.clone()
+                })
+            })
+            .style(theme::slider())
+        };
+
+        let counter_key = {
+            let m = metronome.clone();
+            slider(0..=127, metronome.counter_key, move |counter_key| {
+                Message::MetronomeChanged(MetronomeConfig {
+                    counter_key,
+                    ..m.clone()
+                })
+            })
+            .style(theme::slider())
+        };
+
+        let keys = labeled(
+            format!(
+                "Keys: {} / {} / {}",
+                metronome.accent_key, metronome.regular_key, metronome.counter_key
+            ),
+            row![accent_key, regular_key, counter_key]
+                .spacing(10)
+                .into(),
+        );
+
+        let count_in = {
+            let m = metronome.clone();
+            slider(0..=4, metronome.count_in_bars, move |count_in_bars| {
+                Message::MetronomeChanged(MetronomeConfig {
+                    count_in_bars,
+                    ..m.clone()
+                })
+            })
+            .style(theme::slider())
+        };
+        let count_in = labeled(
+            format!("Count-In: {} bars", metronome.count_in_bars),
+            count_in.into(),
+        );
+
+        // 0 keeps the clicks off the MIDI outputs, the others are channels counted from 1
+        let midi_channel = {
+            let m = metronome.clone();
+            let value = metronome.midi_channel.map_or(0, |channel| channel + 1);
+            slider(0..=16, value, move |value| {
+                Message::MetronomeChanged(MetronomeConfig {
+                    midi_channel: value.checked_sub(1),
+                    ..m.clone()
+                })
+            })
+            .style(theme::slider())
+        };
+        let midi_channel = labeled(
+            match metronome.midi_channel {
+                Some(channel) => format!("MIDI Channel: {}", channel + 1),
+                None => "MIDI Channel: Off".into(),
+            },
+            midi_channel.into(),
+        );
+
+        let mut column = col![pattern, program, keys, count_in, midi_channel].spacing(10);
+
+        if metronome.midi_channel.is_none() {
+            let muted = text("MIDI outputs get no clicks until a channel is chosen.");
+            column = column.push(muted);
+        }
+
+        #[cfg(feature = "sample_kit")]
+        {
+            let sample: Element<'a, Message> = match metronome.sample.as_ref() {
+                Some(sample) => {
+                    let m = metronome.clone();
+                    let file_name = sample
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+
+                    row![
+                        text(file_name)
+                            .vertical_alignment(Vertical::Center)
+                            .height(Length::Units(30))
+                            .width(Length::FillPortion(3)),
+                        button(centered_text("Clear"))
+                            .width(Length::FillPortion(1))
+                            .on_press(Message::MetronomeChanged(MetronomeConfig {
+                                sample: None,
+                                ..m
+                            }))
+                            .style(theme::button()),
+                    ]
+                    .spacing(10)
+                    .into()
+                }
+                None => button(centered_text("Use A Sample"))
+                    .width(Length::Fill)
+                    .on_press(Message::OpenMetronomeSamplePicker)
+                    .style(theme::button())
+                    .into(),
+            };
+
+            column = column.push(labeled("Click Sample:".into(), sample));
+        }
+
+        column.into()
+    }
+
+    fn input_filter_settings(data: &'a Data) -> Element<'a, Message> {
+        let filter = &data.input_filter;
+
+        let min_velocity_title = text(format!("Min Velocity: {}", filter.min_velocity))
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let min_velocity = slider(0..=127, filter.min_velocity, Message::MinVelocitySlider)
+            .width(Length::Fill)
+            .style(theme::slider());
+
+        let min_velocity = row![
+            min_velocity_title.width(Length::Units(120)),
+            min_velocity.width(Length::FillPortion(3))
+        ]
+        .spacing(10);
+
+        let retrigger_title = text(format!("Retrigger: {}ms", filter.retrigger_mask_ms))
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let retrigger = slider(
+            0..=100,
+            filter.retrigger_mask_ms,
+            Message::RetriggerMaskSlider,
+        )
+        .width(Length::Fill)
+        .style(theme::slider());
+
+        let retrigger = row![
+            retrigger_title.width(Length::Units(120)),
+            retrigger.width(Length::FillPortion(3))
+        ]
+        .spacing(10);
+
+        let crosstalk_title = text(format!("Crosstalk: {}ms", filter.crosstalk_window_ms))
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let crosstalk = slider(
+            0..=100,
+            filter.crosstalk_window_ms,
+            Message::CrosstalkWindowSlider,
+        )
+        .width(Length::Fill)
+        .style(theme::slider());
+
+        let crosstalk = row![
+            crosstalk_title.width(Length::Units(120)),
+            crosstalk.width(Length::FillPortion(3))
+        ]
+        .spacing(10);
+
+        let velocity_curve_title = text("Velocity:")
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let velocity_curve = pick_list(
+            &data.velocity_curves,
+            Some(data.selected_velocity_curve.clone()),
+            Message::SelectVelocityCurve,
+        )
+        .width(Length::Fill)
+        .style(theme::pick_list());
+
+        let velocity_curve = row![
+            velocity_curve_title.width(Length::Units(120)),
+            velocity_curve.width(Length::FillPortion(3))
+        ]
+        .spacing(10);
+
+        col![min_velocity, retrigger, crosstalk, velocity_curve]
+            .spacing(10)
+            .into()
+    }
+
+    fn backing_track_settings(data: &'a Data) -> Element<'a, Message> {
+        let title = text("Audio:")
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let backing_track = if let Some(backing_track) = data.backing_track.as_ref() {
+            backing_track
+        } else {
+            let btn = button(centered_text("Attach Backing Track"))
+                .width(Length::Fill)
+                .on_press(Message::OpenBackingTrackPicker)
+                .style(theme::button());
+
+            return row![title.width(Length::Units(120)), btn]
+                .spacing(10)
+                .into();
+        };
+
+        let file_name = backing_track
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let file = row![
+            title.width(Length::Units(120)),
+            text(file_name)
+                .vertical_alignment(Vertical::Center)
+                .height(Length::Units(30))
+                .width(Length::FillPortion(3)),
+            button(centered_text("Clear"))
+                .width(Length::FillPortion(1))
+                .on_press(Message::ClearBackingTrack)
+                .style(theme::button()),
+        ]
+        .spacing(10);
+
+        let offset_title = text(format!("Offset: {:.2}s", backing_track.offset))
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let offset = slider(
+            -10.0..=10.0,
+            backing_track.offset,
+            Message::BackingTrackOffsetSlider,
+        )
+        .step(0.01)
+        .width(Length::Fill)
+        .style(theme::slider());
+
+        let offset = row![
+            offset_title.width(Length::Units(120)),
+            offset.width(Length::FillPortion(3))
+        ]
+        .spacing(10);
+
+        let stretch_title = text(format!("Stretch: {:.3}x", backing_track.time_stretch))
+            .vertical_alignment(Vertical::Center)
+            .height(Length::Units(30));
+
+        let stretch = slider(
+            0.5..=1.5,
+            backing_track.time_stretch,
+            Message::BackingTrackStretchSlider,
+        )
+        .step(0.001)
+        .width(Length::Fill)
+        .style(theme::slider());
+
+        let stretch = row![
+            stretch_title.width(Length::Units(120)),
+            stretch.width(Length::FillPortion(3))
+        ]
+        .spacing(10);
+
+        col![file, offset, stretch].spacing(10).into()
+    }
 }
 
 fn centered_text<'a>(label: impl ToString) -> widget::Text<'a, iced_wgpu::Renderer> {
@@ -602,6 +2220,54 @@ fn open_midi_file_picker(
     )
 }
 
+/// Preset accent patterns, with `accents` appended when it is not one of them.
+fn accent_patterns(accents: &[bool]) -> Vec<AccentPattern> {
+    let mut patterns: Vec<AccentPattern> = ACCENT_PATTERNS
+        .iter()
+        .map(|p| AccentPattern::parse(p))
+        .collect();
+
+    if !patterns.iter().any(|pattern| pattern.0 == accents) {
+        patterns.push(AccentPattern(accents.to_vec()));
+    }
+
+    patterns
+}
+
+/// Preset groupings, with `groups` appended when it is not one of them.
+fn groupings(groups: &[u32]) -> Vec<Grouping> {
+    let mut groupings: Vec<Grouping> = GROUPINGS
+        .iter()
+        .map(|groups| Grouping(groups.to_vec()))
+        .collect();
+
+    if !groupings.iter().any(|grouping| grouping.0 == groups) {
+        groupings.push(Grouping(groups.to_vec()));
+    }
+
+    groupings
+}
+
+/// 2 to 7 clicks against `pulses`, with `counter` appended when it is not one of them.
+fn polyrhythms(counter: u32, pulses: u32) -> Vec<Polyrhythm> {
+    let mut polyrhythms: Vec<Polyrhythm> = std::iter::once(0)
+        .chain((2..=7).filter(|counter| *counter != pulses))
+        .map(|counter| Polyrhythm { counter, pulses })
+        .collect();
+
+    if !polyrhythms.iter().any(|p| p.counter == counter) {
+        polyrhythms.push(Polyrhythm { counter, pulses });
+    }
+
+    polyrhythms
+}
+
+fn audio_choices<T>(values: impl IntoIterator<Item = T>) -> Vec<AudioChoice<T>> {
+    std::iter::once(AudioChoice::Default)
+        .chain(values.into_iter().map(AudioChoice::Custom))
+        .collect()
+}
+
 fn open_sound_font_picker(
     f: impl FnOnce(Option<PathBuf>) -> Message + 'static + Send,
 ) -> Command<Message> where
@@ -610,6 +2276,7 @@ fn open_sound_font_picker(
         async {
             let file = rfd::AsyncFileDialog::new()
                 .add_filter("SoundFont2", &["sf2"])
+                .add_filter("SFZ", &["sfz"])
                 .pick_file()
                 .await;
 
@@ -624,3 +2291,74 @@ fn open_sound_font_picker(
         f,
     )
 }
+
+#[cfg(feature = "sample_kit")]
+fn open_sample_kit_picker(
+    f: impl FnOnce(Option<PathBuf>) -> Message + 'static + Send,
+) -> Command<Message> where
+{
+    Command::perform(
+        async {
+            let file = rfd::AsyncFileDialog::new()
+                .add_filter("Drum Kit", &["ron"])
+                .pick_file()
+                .await;
+
+            if let Some(file) = file.as_ref() {
+                log::info!("Kit path = {:?}", file.path());
+            } else {
+                log::info!("User canceled dialog");
+            }
+
+            file.map(|f| f.path().to_owned())
+        },
+        f,
+    )
+}
+
+#[cfg(feature = "sample_kit")]
+fn open_metronome_sample_picker(
+    f: impl FnOnce(Option<PathBuf>) -> Message + 'static + Send,
+) -> Command<Message> where
+{
+    Command::perform(
+        async {
+            let file = rfd::AsyncFileDialog::new()
+                .add_filter("Sample", &crate::audio::Clip::EXTENSIONS)
+                .pick_file()
+                .await;
+
+            if let Some(file) = file.as_ref() {
+                log::info!("Metronome sample path = {:?}", file.path());
+            } else {
+                log::info!("User canceled dialog");
+            }
+
+            file.map(|f| f.path().to_owned())
+        },
+        f,
+    )
+}
+
+fn open_backing_track_picker(
+    f: impl FnOnce(Option<PathBuf>) -> Message + 'static + Send,
+) -> Command<Message> where
+{
+    Command::perform(
+        async {
+            let file = rfd::AsyncFileDialog::new()
+                .add_filter("Audio", &["wav", "ogg", "flac", "mp3"])
+                .pick_file()
+                .await;
+
+            if let Some(file) = file.as_ref() {
+                log::info!("Backing track path = {:?}", file.path());
+            } else {
+                log::info!("User canceled dialog");
+            }
+
+            file.map(|f| f.path().to_owned())
+        },
+        f,
+    )
+}
@@ -11,7 +11,8 @@ use neothesia_pipelines::background_animation::BgPipeline;
 use winit::event::{MouseButton, WindowEvent};
 
 use crate::{
-    scene::{Scene, SceneType},
+    port_watcher::PortEvent,
+    scene::{toast_manager::ToastManager, Scene, SceneType},
     target::Target,
     ui::{
         iced_conversion,
@@ -27,6 +28,7 @@ pub enum Event {
 pub struct MenuScene {
     bg_pipeline: BgPipeline,
     iced_state: iced_state::State<AppUi>,
+    toast_manager: ToastManager,
 
     context: std::task::Context<'static>,
     futures: Vec<futures::future::BoxFuture<'static, iced_menu::Message>>,
@@ -44,6 +46,7 @@ impl MenuScene {
         let mut scene = Self {
             bg_pipeline: BgPipeline::new(&target.gpu),
             iced_state,
+            toast_manager: ToastManager::default(),
 
             context: std::task::Context::from_waker(futures::task::noop_waker_ref()),
             futures: Vec::new(),
@@ -62,6 +65,7 @@ impl Scene for MenuScene {
     fn update(&mut self, target: &mut Target, delta: Duration) {
         self.bg_pipeline.update_time(&mut target.gpu, delta);
         self.iced_state.queue_message(iced_menu::Message::Tick);
+        self.toast_manager.update(target);
     }
 
     fn render(&mut self, target: &mut Target, view: &wgpu::TextureView) {
@@ -134,6 +138,11 @@ impl Scene for MenuScene {
         }
     }
 
+    fn port_event(&mut self, _target: &mut Target, event: &PortEvent) {
+        self.toast_manager.toast(event.to_string());
+        self.iced_state.queue_message(iced_menu::Message::PortsChanged);
+    }
+
     fn main_events_cleared(&mut self, target: &mut Target) {
         if !self.iced_state.is_queue_empty() {
             if let Some(command) = self.iced_state.update(target) {
@@ -3,10 +3,12 @@ pub mod menu_scene;
 
 pub mod playing_scene;
 
+mod toast_manager;
+
 #[cfg(feature = "app")]
 pub mod scene_manager;
 
-use crate::{target::Target};
+use crate::{input_manager::ClockEvent, port_watcher::PortEvent, target::Target};
 use std::time::Duration;
 use lib_midi::MidiEvent;
 use winit::event::WindowEvent;
@@ -22,6 +24,8 @@ pub trait Scene {
     fn render(&mut self, target: &mut Target, view: &wgpu::TextureView);
     fn window_event(&mut self, _target: &mut Target, _event: &WindowEvent) {}
     fn midi_event(&mut self, _target: &mut Target, _event: &MidiEvent) {}
+    fn clock_event(&mut self, _target: &mut Target, _event: &ClockEvent) {}
+    fn port_event(&mut self, _target: &mut Target, _event: &PortEvent) {}
     fn main_events_cleared(&mut self, _target: &mut Target) {}
 }
 
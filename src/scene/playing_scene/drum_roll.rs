@@ -6,7 +6,7 @@ use crate::{
 use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
 use wgpu_glyph::{GlyphBrush, Section};
 
-use wgpu_jumpstart::Gpu;
+use wgpu_jumpstart::{Color, Gpu};
 
 use super::midi_mapping::{get_midi_mappings, MidiMapping, get_all_midi_mappings};
 
@@ -15,8 +15,10 @@ pub struct DrumRoll {
     size: Size<f32>,
     lanes: Vec<Lane>,
     quad_pipeline: QuadPipeline,
+    meter_pipeline: QuadPipeline,
     should_reupload: bool,
     is_vertical_layout: bool,
+    hihat_openness: Option<f32>,
 }
 
 impl DrumRoll {
@@ -28,6 +30,7 @@ impl DrumRoll {
         is_vertical_layout: bool,
     ) -> Self {
         let quad_pipeline = QuadPipeline::new(gpu, transform_uniform);
+        let meter_pipeline = QuadPipeline::new(gpu, transform_uniform);
 
         let lanes = if is_vertical_layout {
             get_all_midi_mappings().iter().map(|m| Lane::new(*m)).collect()
@@ -43,8 +46,10 @@ impl DrumRoll {
             size: Size { w: window_size.width, h: window_size.height - 5.0 },
             lanes,
             quad_pipeline,
+            meter_pipeline,
             should_reupload: false,
             is_vertical_layout,
+            hihat_openness: None,
         };
 
         drum_roll.resize(window_size);
@@ -107,6 +112,14 @@ impl DrumRoll {
         }
     }
 
+    /// Pedal position shown on the hi-hat lanes, 0.0 closed, 1.0 open.
+    pub fn set_hihat_openness(&mut self, openness: Option<f32>) {
+        if self.hihat_openness != openness {
+            self.hihat_openness = openness;
+            self.queue_reupload();
+        }
+    }
+
     fn queue_reupload(&mut self) {
         self.should_reupload = true;
     }
@@ -171,9 +184,53 @@ impl DrumRoll {
                 });
             }
         });
+        self.reupload_meters(queue);
         self.should_reupload = false;
     }
 
+    fn reupload_meters(&mut self, queue: &wgpu::Queue) {
+        self.meter_pipeline.with_instances_mut(queue, |instances| {
+            instances.clear();
+
+            let openness = if let Some(openness) = self.hihat_openness {
+                openness
+            } else {
+                return;
+            };
+
+            for lane in self.lanes.iter().filter(|lane| lane.is_hihat()) {
+                let color: Color = lane.mapping.color.into();
+
+                let (position, size, fill_position, fill_size) = if self.is_vertical_layout {
+                    let w = lane.size.w * 0.6;
+                    let x = lane.pos.x + (lane.size.w - w) / 2.0;
+                    let y = self.size.h - 10.0;
+                    ([x, y], [w, 4.0], [x, y], [w * openness, 4.0])
+                } else {
+                    let h = lane.size.h * 0.6;
+                    let x = lane.pos.x + 10.0;
+                    let y = lane.pos.y + (lane.size.h - h) / 2.0;
+                    let fill_h = h * openness;
+                    ([x, y], [4.0, h], [x, y + h - fill_h], [4.0, fill_h])
+                };
+
+                instances.push(QuadInstance {
+                    position,
+                    size,
+                    color: [0.05, 0.05, 0.05, 1.0],
+                    ..Default::default()
+                });
+
+                instances.push(QuadInstance {
+                    position: fill_position,
+                    size: fill_size,
+                    color: color.into_linear_rgba(),
+                    ..Default::default()
+                });
+            }
+        });
+    }
+
     pub fn update(&mut self, queue: &wgpu::Queue, brush: &mut GlyphBrush<()>) {
         if self.should_reupload {
             self.reupload(queue);
@@ -209,12 +266,21 @@ impl DrumRoll {
     }
 
     pub fn render<'rpass>(
-        &'rpass mut self,
+        &'rpass self,
         transform_uniform: &'rpass Uniform<TransformUniform>,
         render_pass: &mut wgpu::RenderPass<'rpass>,
     ) {
         self.quad_pipeline.render(transform_uniform, render_pass);
     }
+
+    /// Pedal meters, drawn on top of the dark panel.
+    pub fn render_meters<'rpass>(
+        &'rpass self,
+        transform_uniform: &'rpass Uniform<TransformUniform>,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+    ) {
+        self.meter_pipeline.render(transform_uniform, render_pass);
+    }
 }
 
 pub struct Lane {
@@ -235,4 +301,8 @@ impl Lane {
     pub fn label(&self) -> &str {
         self.mapping.name
     }
+
+    pub fn is_hihat(&self) -> bool {
+        self.mapping.hihat
+    }
 }
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use midly::num::u7;
+
+use super::midi_mapping::get_midi_mapping_for_note;
+
+/// Hi-hat pedal controller (Foot Controller).
+pub const HIHAT_PEDAL_CC: u8 = 4;
+
+/// Openness above which a hi-hat hit counts as open.
+const OPEN_THRESHOLD: f32 = 0.5;
+
+const OPEN_HIHAT_NOTE: u8 = 46;
+const CLOSED_HIHAT_NOTE: u8 = 42;
+
+/// Tracks the hi-hat pedal position sent by the kit as CC4.
+#[derive(Default)]
+pub struct HiHatPedal {
+    /// Last CC4 value, `None` until the kit sends one.
+    value: Option<u8>,
+    /// Notes that were reclassified on note on, so the matching note off is rewritten too.
+    sounding: HashMap<u8, u8>,
+}
+
+impl HiHatPedal {
+    pub fn set_value(&mut self, value: u8) {
+        self.value = Some(value);
+    }
+
+    /// 0.0 fully closed, 1.0 fully open.
+    pub fn openness(&self) -> Option<f32> {
+        self.value.map(|value| 1.0 - value as f32 / 127.0)
+    }
+
+    /// Decide between open and closed hi-hat based on the pedal position.
+    pub fn note_on(&mut self, key: u7) -> u7 {
+        let openness = match self.openness() {
+            Some(openness) => openness,
+            None => return key,
+        };
+
+        let is_hihat = get_midi_mapping_for_note(key.as_int())
+            .map(|mapping| mapping.hihat)
+            .unwrap_or(false);
+
+        if !is_hihat {
+            return key;
+        }
+
+        let note = if openness > OPEN_THRESHOLD {
+            OPEN_HIHAT_NOTE
+        } else {
+            CLOSED_HIHAT_NOTE
+        };
+
+        self.sounding.insert(key.as_int(), note);
+        u7::new(note)
+    }
+
+    pub fn note_off(&mut self, key: u7) -> u7 {
+        self.sounding
+            .remove(&key.as_int())
+            .map(u7::new)
+            .unwrap_or(key)
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::config::InputFilterConfig;
+
+use super::midi_mapping::MidiMapping;
+
+struct Hit {
+    instant: Instant,
+    vel: u8,
+}
+
+/// Crosstalk and double trigger suppression for e-kit input.
+#[derive(Default)]
+pub struct InputFilter {
+    /// Last accepted hit per lane id.
+    last_hits: HashMap<u8, Hit>,
+}
+
+impl InputFilter {
+    /// Returns `false` for hits that should be treated as phantom hits.
+    pub fn accept(
+        &mut self,
+        config: &InputFilterConfig,
+        mapping: &MidiMapping,
+        vel: u8,
+        instant: Instant,
+    ) -> bool {
+        if vel < config.min_velocity(mapping.id) {
+            return false;
+        }
+
+        let retrigger_mask = Duration::from_millis(config.retrigger_mask_ms as u64);
+        let crosstalk_window = Duration::from_millis(config.crosstalk_window_ms as u64);
+
+        for (id, hit) in self.last_hits.iter() {
+            let elapsed = instant.saturating_duration_since(hit.instant);
+
+            if *id == mapping.id {
+                if elapsed < retrigger_mask {
+                    return false;
+                }
+            } else if elapsed < crosstalk_window && hit.vel > vel {
+                return false;
+            }
+        }
+
+        self.last_hits.insert(mapping.id, Hit { instant, vel });
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::playing_scene::midi_mapping::get_midi_mapping_for_note;
+
+    const SNARE: u8 = 38;
+    const KICK: u8 = 36;
+
+    /// Hits sent to a filter, `ms` after the first one.
+    struct Kit {
+        filter: InputFilter,
+        config: InputFilterConfig,
+        start: Instant,
+    }
+
+    impl Kit {
+        fn new(config: InputFilterConfig) -> Self {
+            Self {
+                filter: InputFilter::default(),
+                config,
+                start: Instant::now(),
+            }
+        }
+
+        fn hit(&mut self, note: u8, vel: u8, ms: u64) -> bool {
+            let mapping = get_midi_mapping_for_note(note).unwrap();
+            let instant = self.start + Duration::from_millis(ms);
+            self.filter.accept(&self.config, &mapping, vel, instant)
+        }
+    }
+
+    fn config() -> InputFilterConfig {
+        InputFilterConfig {
+            min_velocity: 10,
+            lane_min_velocity: [(get_midi_mapping_for_note(KICK).unwrap().id, 40)].into(),
+            retrigger_mask_ms: 30,
+            crosstalk_window_ms: 20,
+        }
+    }
+
+    #[test]
+    fn keeps_everything_by_default() {
+        let mut kit = Kit::new(InputFilterConfig::default());
+        assert!(kit.hit(SNARE, 1, 0));
+        assert!(kit.hit(SNARE, 1, 0));
+        assert!(kit.hit(KICK, 1, 0));
+    }
+
+    #[test]
+    fn drops_quiet_hits() {
+        let mut kit = Kit::new(config());
+        assert!(!kit.hit(SNARE, 9, 0));
+        assert!(kit.hit(SNARE, 10, 0));
+    }
+
+    #[test]
+    fn lane_threshold_overrides_global_one() {
+        let mut kit = Kit::new(config());
+        assert!(!kit.hit(KICK, 39, 0));
+        assert!(kit.hit(KICK, 40, 0));
+    }
+
+    #[test]
+    fn drops_retrigger_within_mask() {
+        let mut kit = Kit::new(config());
+        assert!(kit.hit(SNARE, 100, 0));
+        assert!(!kit.hit(SNARE, 100, 29));
+        assert!(kit.hit(SNARE, 100, 30));
+    }
+
+    #[test]
+    fn dropped_retrigger_does_not_extend_mask() {
+        let mut kit = Kit::new(config());
+        assert!(kit.hit(SNARE, 100, 0));
+        assert!(!kit.hit(SNARE, 100, 20));
+        assert!(kit.hit(SNARE, 100, 35));
+    }
+
+    #[test]
+    fn drops_quieter_crosstalk() {
+        let mut kit = Kit::new(config());
+        assert!(kit.hit(SNARE, 100, 0));
+        assert!(!kit.hit(KICK, 60, 10));
+        assert!(kit.hit(KICK, 60, 20));
+    }
+
+    #[test]
+    fn keeps_louder_hit_on_other_lane() {
+        let mut kit = Kit::new(config());
+        assert!(kit.hit(KICK, 60, 0));
+        assert!(kit.hit(SNARE, 100, 5));
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use lib_midi::MidiEvent;
+use midly::MidiMessage;
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode};
+
+use super::{drum_roll::Lane, DRUM_CHANNEL};
+use crate::target::Target;
+
+/// Clicks above this line belong to the progress bar.
+const PROGRESS_BAR_HEIGHT: f32 = 20.0;
+
+const MOUSE_VELOCITY: u8 = 100;
+
+/// Plays drums with the computer keyboard and mouse, producing the same events as a kit.
+#[derive(Default)]
+pub struct KeyboardDrums {
+    /// Keys currently held down, with the note they started.
+    held: HashMap<VirtualKeyCode, u8>,
+    /// Note started by clicking a lane.
+    clicked: Option<u8>,
+}
+
+impl KeyboardDrums {
+    pub fn is_bound(&self, target: &Target, input: &KeyboardInput) -> bool {
+        input
+            .virtual_keycode
+            .map(|key| target.config.keyboard_drums.contains_key(&key))
+            .unwrap_or(false)
+    }
+
+    pub fn keyboard_input(&mut self, target: &Target, input: &KeyboardInput) -> Option<MidiEvent> {
+        let key = input.virtual_keycode?;
+
+        let message = match input.state {
+            ElementState::Pressed => {
+                // Ignore key repeat
+                if self.held.contains_key(&key) {
+                    return None;
+                }
+
+                let binding = target.config.keyboard_drums.get(&key)?;
+                let velocity = if target.window_state.modifers_state.shift() {
+                    127
+                } else {
+                    binding.velocity
+                };
+
+                self.held.insert(key, binding.note);
+                MidiMessage::NoteOn {
+                    key: binding.note.into(),
+                    vel: velocity.max(1).into(),
+                }
+            }
+            ElementState::Released => {
+                let note = self.held.remove(&key)?;
+                MidiMessage::NoteOff {
+                    key: note.into(),
+                    vel: 0.into(),
+                }
+            }
+        };
+
+        Some(target.input_manager.local_event(DRUM_CHANNEL, message))
+    }
+
+    pub fn mouse_input(
+        &mut self,
+        target: &Target,
+        lanes: &[Lane],
+        state: &ElementState,
+        button: &MouseButton,
+    ) -> Option<MidiEvent> {
+        if button != &MouseButton::Left {
+            return None;
+        }
+
+        let message = match state {
+            ElementState::Pressed => {
+                let pos = &target.window_state.cursor_logical_position;
+                if pos.y < PROGRESS_BAR_HEIGHT {
+                    return None;
+                }
+
+                let (x, y) = (pos.x, pos.y);
+                let lane = lanes.iter().find(|lane| {
+                    x >= lane.pos.x
+                        && x < lane.pos.x + lane.size.w
+                        && y >= lane.pos.y
+                        && y < lane.pos.y + lane.size.h
+                })?;
+
+                let note = lane.mapping.note;
+                self.clicked = Some(note);
+                MidiMessage::NoteOn {
+                    key: note.into(),
+                    vel: MOUSE_VELOCITY.into(),
+                }
+            }
+            ElementState::Released => {
+                let note = self.clicked.take()?;
+                MidiMessage::NoteOff {
+                    key: note.into(),
+                    vel: 0.into(),
+                }
+            }
+        };
+
+        Some(target.input_manager.local_event(DRUM_CHANNEL, message))
+    }
+}
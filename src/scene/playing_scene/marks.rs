@@ -27,7 +27,7 @@ impl Marks {
             pipeline,
             is_vertical_layout
         };
-        marks.resize(target, lanes, &Default::default());
+        marks.resize(target, lanes, &Default::default(), &Default::default());
         marks
     }
 
@@ -36,6 +36,7 @@ impl Marks {
         target: &mut Target,
         lanes: &[Lane],
         played_notes: &Vec<(f32, MidiMapping)>,
+        choked_notes: &Vec<(f32, MidiMapping)>,
     ) {
         let mut instances = Vec::new();
 
@@ -63,6 +64,31 @@ impl Marks {
             }
         }
 
+        // Chokes are drawn as thin bars, so they stay visible next to the hit they end
+        for (time, mapping) in choked_notes {
+            if let Some(lane) = lanes.iter().find(|i| i.mapping.id == mapping.id) {
+                let color: Color = default_color_schema().red.into();
+                let mut x = *time;
+                let mut w = 3.0;
+                let mut h = lane.size.h * 0.3;
+                let mut y = lane.pos.y + (lane.size.h / 2.0) - (h / 2.0);
+
+                if self.is_vertical_layout {
+                    y = *time;
+                    w = lane.size.w * 0.3;
+                    h = 3.0;
+                    x = lane.pos.x + (lane.size.w / 2.0) - (w / 2.0);
+                }
+
+                instances.push(NoteInstance {
+                    position: [x, y],
+                    size: [w, h],
+                    color: color.into_linear_rgb(),
+                    radius: 0.0,
+                });
+            }
+        }
+
         self.pipeline
             .update_instance_buffer(&mut target.gpu, instances);
     }
@@ -7,6 +7,8 @@ pub struct MidiMapping {
     pub note: u8,
     pub alt_note: u8,
     pub color: (u8, u8, u8),
+    /// Open or closed hi-hat, switched by the pedal position.
+    pub hihat: bool,
 }
 
 impl MidiMapping {
@@ -38,6 +40,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 49,
         alt_note: 55,
         color: COLOR_SCHEMA.orange1,
+        hihat: false,
     },
     MidiMapping {
         id: 1,
@@ -45,6 +48,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 51,
         alt_note: 59,
         color: COLOR_SCHEMA.orange2,
+        hihat: false,
     },
     MidiMapping {
         id: 2,
@@ -52,6 +56,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 57,
         alt_note: 52,
         color: COLOR_SCHEMA.orange3,
+        hihat: false,
     },
     MidiMapping {
         id: 3,
@@ -59,6 +64,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 53,
         alt_note: 53,
         color: COLOR_SCHEMA.orange4,
+        hihat: false,
     },
     MidiMapping {
         id: 4,
@@ -66,6 +72,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 48,
         alt_note: 50,
         color: COLOR_SCHEMA.purple1,
+        hihat: false,
     },
     MidiMapping {
         id: 5,
@@ -73,6 +80,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 47,
         alt_note: 45,
         color: COLOR_SCHEMA.purple2,
+        hihat: false,
     },
     MidiMapping {
         id: 6,
@@ -80,6 +88,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 41,
         alt_note: 43,
         color: COLOR_SCHEMA.purple3,
+        hihat: false,
     },
     MidiMapping {
         id: 7,
@@ -87,6 +96,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 46,
         alt_note: 26,
         color: COLOR_SCHEMA.green,
+        hihat: true,
     },
     MidiMapping {
         id: 8,
@@ -94,6 +104,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 42,
         alt_note: 22,
         color: COLOR_SCHEMA.beige,
+        hihat: true,
     },
     MidiMapping {
         id: 9,
@@ -101,6 +112,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 38,
         alt_note: 40,
         color: COLOR_SCHEMA.blue,
+        hihat: false,
     },
     MidiMapping {
         id: 10,
@@ -108,6 +120,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 44,
         alt_note: 44,
         color: COLOR_SCHEMA.cyan,
+        hihat: false,
     },
     MidiMapping {
         id: 11,
@@ -115,6 +128,7 @@ const MAPPINGS: [MidiMapping; 12] = [
         note: 35,
         alt_note: 36,
         color: COLOR_SCHEMA.yellow,
+        hihat: false,
     },
 ];
 
@@ -1,6 +1,15 @@
-use crate::{target::Target, OutputManager};
+use crate::{
+    input_manager::ClockEvent, output_manager::OutputGroup, target::Target, OutputManager,
+};
+use lib_midi::MetronomeSettings;
 use midly::MidiMessage;
-use std::{cell::RefCell, collections::HashSet, rc::Rc, time::Duration};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    ops::Range,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 use winit::{
     dpi::PhysicalPosition,
     event::{ElementState, KeyboardInput, MouseButton},
@@ -9,11 +18,40 @@ use winit::{
 mod rewind_controler;
 use rewind_controler::RewindController;
 
+mod external_clock;
+use external_clock::ExternalClock;
+
+mod clock_output;
+use clock_output::ClockOutput;
+
+mod count_in;
+use count_in::CountIn;
+
+mod song_mix;
+use song_mix::SongMix;
+
+/// Larger forward jumps of an external clock are treated as a seek.
+const MAX_CLOCK_JUMP: Duration = Duration::from_secs(1);
+/// Larger backward jumps of an external clock are treated as a seek.
+const MAX_CLOCK_DRIFT: Duration = Duration::from_millis(50);
+/// Shortest time the notes take to fall before the song starts.
+const LEAD_IN: Duration = Duration::from_secs(4);
+
 use super::midi_mapping::get_midi_mapping_for_note;
 
+#[cfg(feature = "backing_track")]
+use crate::backing_track::BackingTrack;
+
 pub struct MidiPlayer {
     playback: lib_midi::PlaybackState,
+    last_update: Instant,
+    /// Speed of the song time since the last update.
+    rate: f32,
     rewind_controller: RewindController,
+    /// Set when the playback follows the MIDI clock of an input.
+    external_clock: Option<ExternalClock>,
+    /// Set when the playback sends its MIDI clock to an output.
+    clock_output: Option<ClockOutput>,
     pub output_manager: Rc<RefCell<OutputManager>>,
     midi_file: Rc<lib_midi::Midi>,
     wait_for_notes: WaitForNotes,
@@ -21,15 +59,65 @@ pub struct MidiPlayer {
     drums_volume: u8,
     music_volume: u8,
     metronome_volume: u8,
+    metronome: MetronomeSettings,
+    /// Next click of `midi_file.metronome` to play.
+    next_click: usize,
+    count_in_bars: u8,
+    /// Set while bars are counted in.
+    count_in: Option<CountIn>,
+    /// Bars of the practice metronome left silent, the guide drums do not play in them.
+    practice_gaps: Vec<Range<Duration>>,
+    mix: SongMix,
+    #[cfg(feature = "backing_track")]
+    backing_track: Option<BackingTrack>,
 }
 
 impl MidiPlayer {
     pub fn new(target: &mut Target) -> Self {
         let midi_file = target.midi_file.as_ref().unwrap();
 
+        #[cfg(feature = "backing_track")]
+        let backing_track = target
+            .config
+            .backing_track(&midi_file.path)
+            .and_then(|config| {
+                let output = target.output_manager.borrow().audio_output()?;
+                match BackingTrack::new(config, &output, target.config.backing_track_volume) {
+                    Ok(track) => Some(track),
+                    Err(err) => {
+                        log::error!("{}", err);
+                        None
+                    }
+                }
+            });
+
+        let mut metronome = target.config.metronome.settings();
+        // Count in the bars of the exercise rather than the song meter
+        if let Some(practice) = &midi_file.practice {
+            metronome.accents = practice.accents();
+        }
+        let count_in_bars = target.config.metronome.count_in_bars;
+
+        // Long enough to count in the first bars
+        let count_in_beats = count_in_bars as u32 * metronome.beats_per_bar() as u32;
+        let count_in =
+            Duration::from_micros(midi_file.tempo_at(Duration::ZERO) as u64) * count_in_beats;
+
         let mut player = Self {
-            playback: lib_midi::PlaybackState::new(Duration::from_secs(4), &midi_file.merged_track),
+            playback: lib_midi::PlaybackState::new(LEAD_IN.max(count_in), &midi_file.merged_track),
+            last_update: Instant::now(),
+            rate: target.config.speed_multiplier,
             rewind_controller: RewindController::None,
+            external_clock: target
+                .config
+                .clock_input
+                .as_ref()
+                .map(|_| ExternalClock::default()),
+            clock_output: target
+                .config
+                .clock_output
+                .as_ref()
+                .and_then(|name| ClockOutput::connect(name)),
             output_manager: target.output_manager.clone(),
             midi_file: midi_file.clone(),
             wait_for_notes: WaitForNotes::default(),
@@ -37,8 +125,33 @@ impl MidiPlayer {
             drums_volume: target.config.drums_volume,
             music_volume: target.config.music_volume,
             metronome_volume: target.config.metronome_volume,
+            metronome,
+            next_click: 0,
+            count_in_bars,
+            count_in: None,
+            practice_gaps: midi_file
+                .practice
+                .as_ref()
+                .map(|practice| practice.gaps(midi_file))
+                .unwrap_or_default(),
+            mix: SongMix::new(
+                target
+                    .config
+                    .mixer(&midi_file.path)
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+            #[cfg(feature = "backing_track")]
+            backing_track,
         };
 
+        #[cfg(feature = "audio")]
+        if target.config.record_sessions {
+            if let Some(path) = session_path(&midi_file.path) {
+                target.output_manager.borrow_mut().start_recording(&path);
+            }
+        }
+
         player.update(target, Duration::ZERO);
 
         player
@@ -55,62 +168,133 @@ impl MidiPlayer {
         rewind_controler::update(self, target);
 
         if delta.is_zero() {
+            let mut output = target.output_manager.borrow_mut();
+            let shared_drums =
+                output.shares_connection(OutputGroup::UserDrums, OutputGroup::GuideDrums);
+
             for channel in 0..16 {
                 let value = if channel == 9 {
                     self.drums_volume
-                } else if channel == 15 {
-                    self.metronome_volume
                 } else {
                     self.music_volume
                 };
-                let msg = MidiMessage::Controller {
-                    controller: midly::num::u7::new(7),
-                    value: midly::num::u7::new(value),
-                };
-                target.output_manager.borrow_mut().midi_event(channel, msg)
+
+                if let Some(msg) = self.mix.volume(channel, value, shared_drums) {
+                    output.midi_event(channel_group(channel), channel, msg);
+                }
+                // The user drums are not part of the song mix
+                if channel == 9 {
+                    output.midi_event(OutputGroup::UserDrums, channel, volume(value));
+                }
+
+                if let Some(pan) = self.mix.pan(channel) {
+                    let msg = MidiMessage::Controller {
+                        controller: midly::num::u7::new(10),
+                        value: midly::num::u7::new(pan),
+                    };
+                    output.midi_event(channel_group(channel), channel, msg);
+                }
             }
+
+            output.metronome_event(self.metronome.program_change());
+            output.metronome_event(volume(self.metronome_volume));
         }
 
-        let elapsed = (delta / 10) * (target.config.speed_multiplier * 10.0) as u32;
+        let mut elapsed = if self.external_clock.is_some() {
+            self.follow_external_clock()
+        } else {
+            (delta / 10) * (target.config.speed_multiplier * 10.0) as u32
+        };
+
+        // The song, the backing track and the clock output wait while the count-in holds
+        let mut holding = false;
+        if let Some(count_in) = self.count_in.as_mut() {
+            let beats = count_in.advance(elapsed);
+            if count_in.hold {
+                elapsed = Duration::ZERO;
+                holding = true;
+            }
+            if count_in.is_done() {
+                self.count_in = None;
+            }
+
+            for beat in beats {
+                self.count_in_click(beat);
+            }
+        }
 
         let events = self.playback.update(&self.midi_file.merged_track, elapsed);
+        self.last_update = Instant::now();
+        self.rate = if holding {
+            0.0
+        } else if let Some(clock) = self.external_clock.as_ref() {
+            let time = self
+                .playback
+                .time()
+                .saturating_sub(*self.playback.leed_in());
+            clock.rate(self.midi_file.tempo_at(time))
+        } else {
+            target.config.speed_multiplier
+        };
+
+        let shared_drums = self
+            .output_manager
+            .borrow()
+            .shares_connection(OutputGroup::UserDrums, OutputGroup::GuideDrums);
 
         events.iter().for_each(|event| {
             let is_drum_channel = event.channel == midly::num::u7::new(9);
             let channel = event.channel;
+            let group = channel_group(channel);
 
             match event.message {
                 MidiMessage::ProgramChange { program: _ } => {
                     self.output_manager
                         .borrow_mut()
-                        .midi_event(channel, event.message);
+                        .midi_event(group, channel, event.message);
                 }
                 MidiMessage::PitchBend { bend: _ } => {
                     self.output_manager
                         .borrow_mut()
-                        .midi_event(channel, event.message);
+                        .midi_event(group, channel, event.message);
                 }
-                MidiMessage::Controller {
-                    controller: _,
-                    value: _,
-                } => {
+                MidiMessage::Controller { controller, value } => {
+                    let message = match controller.as_int() {
+                        // Volume changes of the song are scaled by its mix
+                        7 => match self.mix.volume(channel, value.as_int(), shared_drums) {
+                            Some(message) => message,
+                            None => return,
+                        },
+                        // The mixer pan replaces the pan of the song
+                        10 if self.mix.pan(channel).is_some() => return,
+                        _ => event.message,
+                    };
+
                     self.output_manager
                         .borrow_mut()
-                        .midi_event(channel, event.message);
+                        .midi_event(group, channel, message);
                 }
-                MidiMessage::NoteOn { key, vel: _ } => {
+                MidiMessage::NoteOn { key, vel } => {
                     if is_drum_channel {
                         self.wait_for_notes
                             .press_key(KeyPressSource::File, key.as_int(), true);
                     }
 
-                    if !self.guide_notes && is_drum_channel && event.track_id != 99 {
+                    if !self.guide_notes && is_drum_channel {
+                        return;
+                    }
+                    if is_drum_channel && self.is_practice_gap(event.timestamp) {
                         return;
                     }
 
+                    let message = match self.mix.note_on(channel, key, vel, shared_drums) {
+                        Some(message) => message,
+                        None => return,
+                    };
+
                     self.output_manager
                         .borrow_mut()
-                        .midi_event(channel, event.message);
+                        .midi_event(group, channel, message);
                 }
                 MidiMessage::NoteOff { key, .. } => {
                     if is_drum_channel {
@@ -118,18 +302,34 @@ impl MidiPlayer {
                             .press_key(KeyPressSource::File, key.as_int(), false);
                     }
 
-                    if !self.guide_notes && is_drum_channel && event.track_id != 99 {
+                    if !self.guide_notes && is_drum_channel {
                         return;
                     }
 
                     self.output_manager
                         .borrow_mut()
-                        .midi_event(channel, event.message);
+                        .midi_event(group, channel, event.message);
+                }
+                // Aftertouch, channel aftertouch...
+                _ => {
+                    if !self.guide_notes && is_drum_channel {
+                        return;
+                    }
+
+                    self.output_manager
+                        .borrow_mut()
+                        .midi_event(group, channel, event.message);
                 }
-                _ => {}
             }
         });
 
+        self.play_clicks();
+
+        let playing = !self.playback.is_paused() && !holding;
+        #[cfg(feature = "backing_track")]
+        self.sync_backing_track(target, playing);
+        self.sync_clock_output(target, playing);
+
         if self.playback.is_paused() {
             None
         } else {
@@ -137,10 +337,96 @@ impl MidiPlayer {
         }
     }
 
+    /// Moves the playback to the external clock position,
+    /// returns how far the playback should advance.
+    fn follow_external_clock(&mut self) -> Duration {
+        let clock = if let Some(clock) = self.external_clock.as_ref() {
+            clock
+        } else {
+            return Duration::ZERO;
+        };
+
+        let is_running = clock.is_running();
+        let pulses = clock.pulses(self.midi_file.pulses_per_quarter_note, Instant::now());
+
+        if !is_running {
+            if !self.playback.is_paused() {
+                self.pause();
+            }
+            return Duration::ZERO;
+        }
+
+        if self.playback.is_paused() {
+            self.resume();
+        }
+
+        let time = self.playback.time();
+        let clock_time = self.midi_file.pulses_to_duration(pulses) + *self.playback.leed_in();
+
+        if clock_time > time + MAX_CLOCK_JUMP || clock_time + MAX_CLOCK_DRIFT < time {
+            self.set_time(clock_time);
+            Duration::ZERO
+        } else {
+            // Small backward corrections are absorbed by waiting
+            clock_time.saturating_sub(time)
+        }
+    }
+
+    /// Count-in at the tempo of the current position, `None` when it is turned off
+    /// or an external clock decides when the song plays.
+    fn new_count_in(&self, hold: bool) -> Option<CountIn> {
+        if self.count_in_bars == 0 || self.external_clock.is_some() {
+            return None;
+        }
+
+        let time = self
+            .playback
+            .time()
+            .saturating_sub(*self.playback.leed_in());
+        let beat = Duration::from_micros(self.midi_file.tempo_at(time) as u64);
+        let beats = self.count_in_bars as usize * self.metronome.beats_per_bar();
+
+        Some(CountIn::new(beat, beats, hold))
+    }
+
+    fn count_in_click(&self, beat: usize) {
+        let mut output = self.output_manager.borrow_mut();
+
+        for message in self
+            .metronome
+            .click_events(self.metronome.click_kind(beat, true))
+        {
+            output.metronome_event(message);
+        }
+    }
+
+    /// Play the clicks of the metronome the playback went past.
+    fn play_clicks(&mut self) {
+        let clicks = &self.midi_file.metronome.clicks;
+        let time = self.playback.time();
+        let leed_in = *self.playback.leed_in();
+
+        let mut output = self.output_manager.borrow_mut();
+        while let Some(click) = clicks
+            .get(self.next_click)
+            .filter(|click| click.timestamp + leed_in <= time)
+        {
+            for message in self.metronome.click_events(click.kind) {
+                output.metronome_event(message);
+            }
+            self.next_click += 1;
+        }
+    }
+
+    fn is_practice_gap(&self, time: Duration) -> bool {
+        self.practice_gaps.iter().any(|gap| gap.contains(&time))
+    }
+
     fn clear(&mut self) {
         let mut output = self.output_manager.borrow_mut();
         for note in self.playback.active_notes().iter() {
             output.midi_event(
+                channel_group(note.channel),
                 note.channel,
                 MidiMessage::NoteOff {
                     key: note.key,
@@ -151,15 +437,54 @@ impl MidiPlayer {
     }
 }
 
+fn channel_group(channel: u8) -> OutputGroup {
+    match channel {
+        9 => OutputGroup::GuideDrums,
+        _ => OutputGroup::Music,
+    }
+}
+
+/// Channel volume controller.
+fn volume(value: u8) -> MidiMessage {
+    MidiMessage::Controller {
+        controller: midly::num::u7::new(7),
+        value: midly::num::u7::new(value),
+    }
+}
+
+/// New file in the sessions folder, named after the song and the time it was played.
+#[cfg(feature = "audio")]
+fn session_path(midi: &std::path::Path) -> Option<std::path::PathBuf> {
+    let song = midi.file_stem()?.to_string_lossy();
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+
+    crate::utils::resources::sessions_dir()
+        .map(|dir| dir.join(format!("{}-{}.wav", song, timestamp)))
+}
+
 impl Drop for MidiPlayer {
     fn drop(&mut self) {
         self.clear();
+
+        let mut output = self.output_manager.borrow_mut();
+        output.stop_all();
+        #[cfg(feature = "audio")]
+        output.stop_recording();
     }
 }
 
 impl MidiPlayer {
     pub fn start(&mut self) {
-        self.resume();
+        self.playback.resume();
+
+        // Counted in at the end of the lead-in, while the first notes fall
+        let lead_in_left = self.playback.leed_in().saturating_sub(self.playback.time());
+        self.count_in = self
+            .new_count_in(false)
+            .map(|count_in| count_in.ending_in(lead_in_left));
     }
 
     pub fn pause_resume(&mut self) {
@@ -173,15 +498,70 @@ impl MidiPlayer {
     pub fn pause(&mut self) {
         self.clear();
         self.playback.pause();
+        self.count_in = None;
+    }
+
+    /// Called instead of `update` while playback is held back by wait-for-notes.
+    pub fn hold(&mut self, target: &mut Target) {
+        self.last_update = Instant::now();
+
+        #[cfg(feature = "backing_track")]
+        self.sync_backing_track(target, false);
+        self.sync_clock_output(target, false);
+    }
+
+    #[cfg(feature = "backing_track")]
+    fn sync_backing_track(&mut self, target: &Target, playing: bool) {
+        let time = self.time_without_lead_in();
+        if let Some(backing_track) = self.backing_track.as_mut() {
+            backing_track.sync(time, target.config.speed_multiplier, playing);
+        }
+    }
+
+    fn sync_clock_output(&mut self, target: &Target, playing: bool) {
+        let clock_output = if let Some(clock_output) = self.clock_output.as_mut() {
+            clock_output
+        } else {
+            return;
+        };
+
+        // Nothing to send during the lead-in, the song starts at position 0
+        let time = if let Some(time) = self.playback.time().checked_sub(*self.playback.leed_in()) {
+            time
+        } else {
+            clock_output.sync(false, 0.0, 0.0);
+            return;
+        };
+
+        // An external clock already sets the pace
+        let speed = if self.external_clock.is_some() {
+            1.0
+        } else {
+            target.config.speed_multiplier as f64
+        };
+
+        let ppq = self.midi_file.pulses_per_quarter_note as f64;
+        let ticks = self.midi_file.duration_to_pulses(time) as f64 / ppq * 24.0;
+        let rate = 24.0 * 1_000_000.0 / self.midi_file.tempo_at(time) as f64 * speed;
+
+        clock_output.sync(playing, ticks, rate);
     }
 
     pub fn resume(&mut self) {
         self.playback.resume();
+        self.count_in = self.new_count_in(true);
     }
 
     fn set_time(&mut self, time: Duration) {
         self.playback.set_time(time);
 
+        let leed_in = *self.playback.leed_in();
+        self.next_click = self
+            .midi_file
+            .metronome
+            .clicks
+            .partition_point(|click| click.timestamp + leed_in < time);
+
         let events = self
             .playback
             .update(&self.midi_file.merged_track, Duration::ZERO);
@@ -218,9 +598,31 @@ impl MidiPlayer {
         self.playback.time().as_secs_f32() - self.playback.leed_in().as_secs_f32()
     }
 
+    /// Song time (without lead-in) at `instant`, extrapolated from the last update,
+    /// so input events are not snapped to frame boundaries.
+    pub fn time_without_lead_in_at(&self, instant: Instant) -> f32 {
+        let time = self.time_without_lead_in();
+
+        if self.playback.is_paused() {
+            return time;
+        }
+
+        if instant >= self.last_update {
+            time + (instant - self.last_update).as_secs_f32() * self.rate
+        } else {
+            time - (self.last_update - instant).as_secs_f32() * self.rate
+        }
+    }
+
     pub fn is_paused(&self) -> bool {
         self.playback.is_paused()
     }
+
+    pub fn clock_event(&mut self, target: &Target, event: &ClockEvent) {
+        if let Some(clock) = self.external_clock.as_mut() {
+            clock.clock_event(event.message, target.input_manager.clock_instant(event));
+        }
+    }
 }
 
 impl MidiPlayer {
@@ -0,0 +1,187 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::input_manager::ClockMessage;
+
+/// MIDI clock ticks per MIDI beat (16th note).
+const TICKS_PER_BEAT: f64 = 6.0;
+/// Larger differences between the sent clock and the playback restart the transport.
+const MAX_DRIFT_TICKS: f64 = 6.0;
+/// Longest sleep of the sender thread, so transport changes go out quickly.
+const IDLE_INTERVAL: Duration = Duration::from_millis(2);
+/// Song Position Pointer is a 14 bit value.
+const MAX_SONG_POSITION: f64 = 16383.0;
+
+/// Sends MIDI clock and transport of the playback to an output port.
+///
+/// Ticks are sent from a dedicated thread, so they do not jitter with the frame rate.
+pub struct ClockOutput {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    alive: bool,
+    /// Transport messages waiting to be sent.
+    pending: Vec<ClockMessage>,
+    running: bool,
+    /// Song position in ticks at `anchor`.
+    anchor_ticks: f64,
+    anchor: Instant,
+    /// Ticks per second.
+    rate: f64,
+    /// Song position of the next tick to send.
+    next_tick: u64,
+}
+
+impl State {
+    fn ticks_at(&self, instant: Instant) -> f64 {
+        self.anchor_ticks + instant.saturating_duration_since(self.anchor).as_secs_f64() * self.rate
+    }
+}
+
+impl ClockOutput {
+    /// Connect to the MIDI output port called `name`.
+    pub fn connect(name: &str) -> Option<Self> {
+        let manager = match midi_io::MidiOutputManager::new() {
+            Ok(manager) => manager,
+            Err(err) => {
+                log::error!("{}", err);
+                return None;
+            }
+        };
+
+        let port = manager
+            .outputs()
+            .into_iter()
+            .find(|port| port.to_string() == name)?;
+
+        let conn = match midi_io::MidiOutputManager::connect_output(port) {
+            Some(conn) => conn,
+            None => {
+                log::error!("failed to connect clock output to {}", name);
+                return None;
+            }
+        };
+
+        let state = Arc::new(Mutex::new(State {
+            alive: true,
+            pending: Vec::new(),
+            running: false,
+            anchor_ticks: 0.0,
+            anchor: Instant::now(),
+            rate: 0.0,
+            next_tick: 0,
+        }));
+
+        let thread_state = state.clone();
+        let spawned = thread::Builder::new()
+            .name("midi-clock-output".into())
+            .spawn(move || run(thread_state, conn));
+
+        if let Err(err) = spawned {
+            log::error!("{}", err);
+            return None;
+        }
+
+        Some(Self { state })
+    }
+
+    /// Follow the playback: `ticks` is the song position in clock ticks (24 per quarter note),
+    /// `rate` the current tempo in ticks per second.
+    pub fn sync(&mut self, playing: bool, ticks: f64, rate: f64) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        if !playing {
+            if state.running {
+                state.running = false;
+                state.pending.push(ClockMessage::Stop);
+            }
+            return;
+        }
+
+        if state.running && (state.ticks_at(now) - ticks).abs() > MAX_DRIFT_TICKS {
+            // Seek, move the receiver to the new position
+            state.running = false;
+            state.pending.push(ClockMessage::Stop);
+        }
+
+        if !state.running {
+            // Song Position Pointer only addresses 16th notes,
+            // the transport continues on the next one
+            let beat = (ticks / TICKS_PER_BEAT)
+                .ceil()
+                .clamp(0.0, MAX_SONG_POSITION) as u16;
+
+            if beat == 0 {
+                state.pending.push(ClockMessage::Start);
+            } else {
+                state.pending.push(ClockMessage::SongPosition(beat));
+                state.pending.push(ClockMessage::Continue);
+            }
+
+            state.next_tick = beat as u64 * TICKS_PER_BEAT as u64;
+            state.running = true;
+        }
+
+        state.anchor = now;
+        state.anchor_ticks = ticks;
+        state.rate = rate;
+    }
+}
+
+impl Drop for ClockOutput {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        if state.running {
+            state.pending.push(ClockMessage::Stop);
+        }
+        state.alive = false;
+    }
+}
+
+fn run(state: Arc<Mutex<State>>, mut conn: midi_io::MidiOutputConnection) {
+    let mut send = |message: ClockMessage| {
+        let mut data = Vec::new();
+        message.to_live_event().write(&mut data).unwrap();
+        conn.send(&data).ok();
+    };
+
+    loop {
+        let wait = {
+            let mut state = state.lock().unwrap();
+
+            for message in std::mem::take(&mut state.pending) {
+                send(message);
+            }
+
+            if !state.alive {
+                break;
+            }
+
+            if !state.running || state.rate <= 0.0 {
+                IDLE_INTERVAL
+            } else {
+                let ticks = state.ticks_at(Instant::now());
+
+                if ticks - state.next_tick as f64 > MAX_DRIFT_TICKS {
+                    // Tempo jumped ahead of the sent ticks, skip instead of bursting
+                    state.next_tick = ticks as u64;
+                }
+
+                while state.next_tick as f64 <= ticks {
+                    send(ClockMessage::Tick);
+                    state.next_tick += 1;
+                }
+
+                let until_next = (state.next_tick as f64 - ticks) / state.rate;
+                Duration::from_secs_f64(until_next).min(IDLE_INTERVAL)
+            }
+        };
+
+        thread::sleep(wait);
+    }
+}
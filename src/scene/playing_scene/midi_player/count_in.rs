@@ -0,0 +1,128 @@
+use std::{ops::Range, time::Duration};
+
+/// Clicks on every beat of the bars counted in before the song or a resume.
+pub struct CountIn {
+    /// Time left before the first click.
+    delay: Duration,
+    elapsed: Duration,
+    beat: Duration,
+    beats: usize,
+    clicked: usize,
+    /// Whether the playback waits for the count-in to end.
+    pub hold: bool,
+}
+
+impl CountIn {
+    pub fn new(beat: Duration, beats: usize, hold: bool) -> Self {
+        Self {
+            delay: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            beat,
+            beats,
+            clicked: 0,
+            hold,
+        }
+    }
+
+    /// Counted in so it ends `ends_in` from now.
+    pub fn ending_in(mut self, ends_in: Duration) -> Self {
+        self.delay = ends_in.saturating_sub(self.length());
+        self
+    }
+
+    pub fn length(&self) -> Duration {
+        self.beat * self.beats as u32
+    }
+
+    /// Advances by `delta`, returns the beats to click.
+    pub fn advance(&mut self, delta: Duration) -> Range<usize> {
+        let waited = delta.min(self.delay);
+        self.delay -= waited;
+        self.elapsed += delta - waited;
+
+        let first = self.clicked;
+        while self.delay.is_zero()
+            && self.clicked < self.beats
+            && self.beat * self.clicked as u32 <= self.elapsed
+        {
+            self.clicked += 1;
+        }
+
+        first..self.clicked
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.delay.is_zero() && self.elapsed >= self.length()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEAT: Duration = Duration::from_millis(500);
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn clicks_every_beat() {
+        let mut count_in = CountIn::new(BEAT, 4, true);
+        assert_eq!(count_in.length(), ms(2000));
+
+        assert_eq!(count_in.advance(Duration::ZERO), 0..1);
+        assert_eq!(count_in.advance(ms(499)), 1..1);
+        assert_eq!(count_in.advance(ms(1)), 1..2);
+        assert_eq!(count_in.advance(ms(1000)), 2..4);
+        assert!(!count_in.is_done());
+
+        assert_eq!(count_in.advance(ms(500)), 4..4);
+        assert!(count_in.is_done());
+    }
+
+    #[test]
+    fn long_frame_clicks_every_beat_it_passes() {
+        let mut count_in = CountIn::new(BEAT, 4, false);
+        assert_eq!(count_in.advance(ms(5000)), 0..4);
+        assert!(count_in.is_done());
+    }
+
+    #[test]
+    fn ending_in_waits_before_first_click() {
+        let mut count_in = CountIn::new(BEAT, 2, false).ending_in(ms(1500));
+
+        assert_eq!(count_in.advance(ms(499)), 0..0);
+        assert_eq!(count_in.advance(ms(1)), 0..1);
+        assert!(!count_in.is_done());
+
+        assert_eq!(count_in.advance(ms(500)), 1..2);
+        assert_eq!(count_in.advance(ms(500)), 2..2);
+        assert!(count_in.is_done());
+    }
+
+    #[test]
+    fn ending_in_shorter_than_count_in_starts_right_away() {
+        let mut count_in = CountIn::new(BEAT, 4, false).ending_in(ms(1000));
+        assert_eq!(count_in.advance(Duration::ZERO), 0..1);
+        assert_eq!(count_in.advance(ms(2000)), 1..4);
+        assert!(count_in.is_done());
+    }
+
+    #[test]
+    fn delay_is_not_counted_in_beats() {
+        let mut count_in = CountIn::new(BEAT, 2, true).ending_in(ms(1200));
+
+        // The frame crossing the end of the delay only counts the time past it
+        assert_eq!(count_in.advance(ms(300)), 0..1);
+        assert_eq!(count_in.advance(ms(399)), 1..1);
+        assert_eq!(count_in.advance(ms(1)), 1..2);
+    }
+
+    #[test]
+    fn empty_count_in_is_done() {
+        let mut count_in = CountIn::new(BEAT, 0, true);
+        assert!(count_in.is_done());
+        assert_eq!(count_in.advance(ms(100)), 0..0);
+    }
+}
@@ -0,0 +1,236 @@
+use std::time::{Duration, Instant};
+
+use crate::input_manager::ClockMessage;
+
+/// MIDI clock ticks per quarter note.
+const TICKS_PER_QUARTER: f64 = 24.0;
+/// MIDI clock ticks per MIDI beat (16th note).
+const TICKS_PER_BEAT: u64 = 6;
+
+/// Song position driven by an external MIDI clock.
+#[derive(Default)]
+pub struct ExternalClock {
+    running: bool,
+    /// Ticks since the start of the song.
+    ticks: u64,
+    /// The first tick after Start/Continue marks the current position, it does not advance it.
+    waiting_first_tick: bool,
+    last_tick: Option<Instant>,
+    /// Smoothed time between two ticks.
+    tick_interval: Option<Duration>,
+}
+
+impl ExternalClock {
+    pub fn clock_event(&mut self, message: ClockMessage, instant: Instant) {
+        match message {
+            ClockMessage::Start => {
+                self.ticks = 0;
+                self.running = true;
+                self.waiting_first_tick = true;
+            }
+            ClockMessage::Continue => {
+                self.running = true;
+                self.waiting_first_tick = true;
+            }
+            ClockMessage::Stop => {
+                self.running = false;
+            }
+            ClockMessage::SongPosition(beats) => {
+                self.ticks = beats as u64 * TICKS_PER_BEAT;
+                self.last_tick = None;
+            }
+            ClockMessage::Tick => {
+                if let Some(last_tick) = self.last_tick {
+                    let interval = instant.saturating_duration_since(last_tick);
+                    self.tick_interval = Some(match self.tick_interval {
+                        Some(smoothed) => (smoothed * 7 + interval) / 8,
+                        None => interval,
+                    });
+                }
+                self.last_tick = Some(instant);
+
+                if self.running {
+                    if self.waiting_first_tick {
+                        self.waiting_first_tick = false;
+                    } else {
+                        self.ticks += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Speed the clock plays a song written at `tempo` (microseconds per quarter note),
+    /// 1.0 until the time between ticks is known.
+    pub fn rate(&self, tempo: u32) -> f32 {
+        match self.tick_interval {
+            Some(interval) if !interval.is_zero() => {
+                let quarter = interval.as_secs_f64() * TICKS_PER_QUARTER;
+                (tempo as f64 / 1_000_000.0 / quarter) as f32
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Song position in pulses at `now`, interpolated between ticks
+    /// but never past the next expected tick.
+    pub fn pulses(&self, pulses_per_quarter_note: u16, now: Instant) -> u64 {
+        let advancing = self.running && !self.waiting_first_tick;
+
+        let fract = match (advancing, self.last_tick, self.tick_interval) {
+            (true, Some(last_tick), Some(interval)) if !interval.is_zero() => {
+                let elapsed = now.saturating_duration_since(last_tick);
+                (elapsed.as_secs_f64() / interval.as_secs_f64()).min(0.999)
+            }
+            _ => 0.0,
+        };
+
+        let quarters = (self.ticks as f64 + fract) / TICKS_PER_QUARTER;
+        (quarters * pulses_per_quarter_note as f64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PPQ: u16 = 480;
+
+    /// Time between two ticks at `bpm`.
+    fn tick_interval(bpm: f64) -> Duration {
+        Duration::from_secs_f64(60.0 / bpm / TICKS_PER_QUARTER)
+    }
+
+    /// Sends `count` ticks at `bpm` after `start`, returns the time of the last one.
+    fn send_ticks(clock: &mut ExternalClock, start: Instant, bpm: f64, count: u32) -> Instant {
+        let mut instant = start;
+        for _ in 0..count {
+            instant += tick_interval(bpm);
+            clock.clock_event(ClockMessage::Tick, instant);
+        }
+        instant
+    }
+
+    fn bpm(clock: &ExternalClock) -> f64 {
+        60.0 / (clock.tick_interval.unwrap().as_secs_f64() * TICKS_PER_QUARTER)
+    }
+
+    #[test]
+    fn first_tick_after_start_marks_the_start() {
+        let mut clock = ExternalClock::default();
+        let now = Instant::now();
+
+        clock.clock_event(ClockMessage::Start, now);
+        clock.clock_event(ClockMessage::Tick, now);
+        assert_eq!(clock.pulses(PPQ, now), 0);
+
+        let last = send_ticks(&mut clock, now, 120.0, 24);
+        assert_eq!(clock.pulses(PPQ, last), PPQ as u64);
+    }
+
+    #[test]
+    fn ticks_are_ignored_until_start() {
+        let mut clock = ExternalClock::default();
+        let last = send_ticks(&mut clock, Instant::now(), 120.0, 48);
+
+        assert!(!clock.is_running());
+        assert_eq!(clock.pulses(PPQ, last), 0);
+    }
+
+    #[test]
+    fn tracks_tempo() {
+        let mut clock = ExternalClock::default();
+        let now = Instant::now();
+
+        let last = send_ticks(&mut clock, now, 120.0, 48);
+        assert!((bpm(&clock) - 120.0).abs() < 0.1);
+
+        // The smoothed interval follows a tempo change within a couple of beats
+        send_ticks(&mut clock, last, 140.0, 48);
+        assert!((bpm(&clock) - 140.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn rate_follows_the_clock_against_the_song_tempo() {
+        let mut clock = ExternalClock::default();
+        assert_eq!(clock.rate(500_000), 1.0);
+
+        // A song written at 120 BPM driven at 150 BPM
+        send_ticks(&mut clock, Instant::now(), 150.0, 48);
+        assert!((clock.rate(500_000) - 1.25).abs() < 0.01);
+    }
+
+    #[test]
+    fn smooths_jitter() {
+        let mut clock = ExternalClock::default();
+        let mut instant = Instant::now();
+
+        for id in 0..96 {
+            // Ticks alternate 2ms early and 2ms late around 120 BPM
+            let interval = tick_interval(120.0);
+            let jitter = Duration::from_millis(2);
+            instant += if id % 2 == 0 {
+                interval - jitter
+            } else {
+                interval + jitter
+            };
+            clock.clock_event(ClockMessage::Tick, instant);
+        }
+
+        assert!((bpm(&clock) - 120.0).abs() < 3.0);
+    }
+
+    #[test]
+    fn interpolates_between_ticks() {
+        let mut clock = ExternalClock::default();
+        let now = Instant::now();
+
+        clock.clock_event(ClockMessage::Start, now);
+        clock.clock_event(ClockMessage::Tick, now);
+        let last = send_ticks(&mut clock, now, 120.0, 24);
+
+        // Half a tick later
+        let half_tick = last + tick_interval(120.0) / 2;
+        let pulses = clock.pulses(PPQ, half_tick);
+        assert!(pulses.abs_diff(PPQ as u64 + 10) <= 1);
+
+        // A late tick does not let the position run past the next one
+        let late = last + tick_interval(120.0) * 10;
+        assert!(clock.pulses(PPQ, late) < PPQ as u64 + 20);
+    }
+
+    #[test]
+    fn stop_holds_position_and_continue_resumes() {
+        let mut clock = ExternalClock::default();
+        let now = Instant::now();
+
+        clock.clock_event(ClockMessage::Start, now);
+        clock.clock_event(ClockMessage::Tick, now);
+        let last = send_ticks(&mut clock, now, 120.0, 24);
+
+        clock.clock_event(ClockMessage::Stop, last);
+        let last = send_ticks(&mut clock, last, 120.0, 24);
+        assert_eq!(clock.pulses(PPQ, last), PPQ as u64);
+
+        clock.clock_event(ClockMessage::Continue, last);
+        let last = send_ticks(&mut clock, last, 120.0, 25);
+        assert_eq!(clock.pulses(PPQ, last), PPQ as u64 * 2);
+    }
+
+    #[test]
+    fn song_position_moves_to_beat() {
+        let mut clock = ExternalClock::default();
+        let now = Instant::now();
+
+        // Bar 2 of a 4/4 song, 16 sixteenth notes in
+        clock.clock_event(ClockMessage::SongPosition(16), now);
+        clock.clock_event(ClockMessage::Continue, now);
+        clock.clock_event(ClockMessage::Tick, now);
+
+        assert_eq!(clock.pulses(PPQ, now), PPQ as u64 * 4);
+    }
+}
@@ -0,0 +1,130 @@
+use midly::{num::u7, MidiMessage};
+
+use crate::{config::MixerConfig, output_manager::OutputGroup};
+
+use super::{channel_group, volume};
+
+/// Mixer of the song on its way to the outputs.
+///
+/// The user hits play on the drum channel as well. When they go through the same connection
+/// as the guide drums the volume of that channel is theirs, and the guide drums are mixed
+/// through the velocity of their notes instead.
+pub struct SongMix {
+    mixer: MixerConfig,
+}
+
+impl SongMix {
+    pub fn new(mixer: MixerConfig) -> Self {
+        Self { mixer }
+    }
+
+    /// Pan controller value of `channel`, `None` keeps the pan of the file.
+    pub fn pan(&self, channel: u8) -> Option<u8> {
+        self.mixer.pan(channel)
+    }
+
+    /// Volume controller for `value` scaled by the channel mix,
+    /// `None` when the channel volume belongs to the user hits.
+    pub fn volume(&self, channel: u8, value: u8, shared_drums: bool) -> Option<MidiMessage> {
+        if shared_drums && is_drum_channel(channel) {
+            return None;
+        }
+
+        let value = (value as f32 * self.mixer.gain(channel)).round() as u8;
+        Some(volume(value.min(127)))
+    }
+
+    /// Note on of the song, scaled by the channel mix when the channel volume belongs to the
+    /// user hits, `None` when the mix silences it.
+    pub fn note_on(
+        &self,
+        channel: u8,
+        key: u7,
+        vel: u7,
+        shared_drums: bool,
+    ) -> Option<MidiMessage> {
+        // A note on without velocity is a note off
+        if !shared_drums || !is_drum_channel(channel) || vel == 0 {
+            return Some(MidiMessage::NoteOn { key, vel });
+        }
+
+        let vel = (vel.as_int() as f32 * self.mixer.gain(channel)).round() as u8;
+        if vel == 0 {
+            return None;
+        }
+
+        Some(MidiMessage::NoteOn {
+            key,
+            vel: u7::new(vel.min(127)),
+        })
+    }
+}
+
+fn is_drum_channel(channel: u8) -> bool {
+    channel_group(channel) == OutputGroup::GuideDrums
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mix(channel: u8, f: impl FnOnce(&mut crate::config::ChannelMix)) -> SongMix {
+        let mut mixer = MixerConfig::default();
+        f(mixer.channel_mut(channel));
+        SongMix::new(mixer)
+    }
+
+    fn note_on(vel: u8) -> MidiMessage {
+        MidiMessage::NoteOn {
+            key: u7::new(38),
+            vel: u7::new(vel),
+        }
+    }
+
+    fn note_on_at(mix: &SongMix, channel: u8, vel: u8, shared_drums: bool) -> Option<MidiMessage> {
+        mix.note_on(channel, u7::new(38), u7::new(vel), shared_drums)
+    }
+
+    #[test]
+    fn volume_is_scaled_by_the_mix() {
+        let mix = mix(2, |channel| channel.volume = 64);
+        assert_eq!(mix.volume(2, 100, false), Some(volume(50)));
+        assert_eq!(mix.volume(3, 100, false), Some(volume(100)));
+        assert_eq!(mix.volume(2, 100, true), Some(volume(50)));
+    }
+
+    #[test]
+    fn drum_volume_is_mixed_on_its_own_connection() {
+        let mix = mix(9, |channel| channel.mute = true);
+        assert_eq!(mix.volume(9, 100, false), Some(volume(0)));
+        assert_eq!(note_on_at(&mix, 9, 100, false), Some(note_on(100)));
+    }
+
+    #[test]
+    fn muted_drums_keep_the_user_hits_level() {
+        let mix = mix(9, |channel| channel.mute = true);
+
+        // The drum channel volume is left to the user hits
+        assert_eq!(mix.volume(9, 100, true), None);
+        assert_eq!(mix.volume(9, 0, true), None);
+        // The guide drums are silenced note by note instead
+        assert_eq!(note_on_at(&mix, 9, 100, true), None);
+    }
+
+    #[test]
+    fn solo_keeps_the_user_hits_level() {
+        let mix = mix(1, |channel| channel.solo = true);
+
+        assert_eq!(mix.volume(9, 100, true), None);
+        assert_eq!(note_on_at(&mix, 9, 100, true), None);
+        assert_eq!(note_on_at(&mix, 1, 100, true), Some(note_on(100)));
+    }
+
+    #[test]
+    fn shared_drums_are_mixed_by_velocity() {
+        let mix = mix(9, |channel| channel.volume = 64);
+        assert_eq!(note_on_at(&mix, 9, 100, true), Some(note_on(50)));
+        // Note offs sent as note ons pass through
+        assert_eq!(note_on_at(&mix, 9, 0, true), Some(note_on(0)));
+    }
+}
@@ -1,7 +1,7 @@
 use lib_midi::MidiEvent;
 use midly::MidiMessage;
 use neothesia_pipelines::quad::{QuadInstance, QuadPipeline};
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 use wgpu_jumpstart::Color;
 use winit::{
     dpi::LogicalSize,
@@ -13,13 +13,25 @@ use self::{
     midi_mapping::{get_midi_mapping_for_note, MidiMapping},
 };
 
-use super::{Scene, SceneType};
-use crate::{target::Target, NeothesiaEvent, config::PlayingSceneLayout};
+use super::{toast_manager::ToastManager, Scene, SceneType};
+use crate::{
+    config::PlayingSceneLayout, input_manager::ClockEvent, output_manager::OutputGroup,
+    port_watcher::PortEvent, target::Target, NeothesiaEvent,
+};
 
 mod drum_roll;
 mod marks;
 use drum_roll::DrumRoll;
 
+mod hihat;
+use hihat::{HiHatPedal, HIHAT_PEDAL_CC};
+
+mod input_filter;
+use input_filter::InputFilter;
+
+mod keyboard_drums;
+use keyboard_drums::KeyboardDrums;
+
 mod notes;
 use notes::Notes;
 
@@ -28,8 +40,8 @@ use midi_player::MidiPlayer;
 
 mod midi_mapping;
 
-mod toast_manager;
-use toast_manager::ToastManager;
+/// User hits are played on the GM drum channel, whatever channel the kit sends them on.
+const DRUM_CHANNEL: u8 = 9;
 
 pub struct PlayingScene {
     drum_roll: DrumRoll,
@@ -37,6 +49,12 @@ pub struct PlayingScene {
     marks: Marks,
     player: MidiPlayer,
     played_notes: Vec<(f32, MidiMapping)>,
+    choked_notes: Vec<(f32, MidiMapping)>,
+    /// Keys currently held by a cymbal grab.
+    choked_keys: HashSet<u8>,
+    hihat: HiHatPedal,
+    input_filter: InputFilter,
+    keyboard_drums: KeyboardDrums,
     quad_pipeline: QuadPipeline,
     toast_manager: ToastManager,
 }
@@ -76,6 +94,11 @@ impl PlayingScene {
             marks,
             player,
             played_notes: Default::default(),
+            choked_notes: Default::default(),
+            choked_keys: Default::default(),
+            hihat: HiHatPedal::default(),
+            input_filter: InputFilter::default(),
+            keyboard_drums: KeyboardDrums::default(),
             quad_pipeline: QuadPipeline::new(&target.gpu, &target.transform_uniform),
             toast_manager: ToastManager::default(),
         }
@@ -157,14 +180,20 @@ impl Scene for PlayingScene {
         let (width, height) = target.window_state.logical_size.into();
         self.drum_roll.resize(LogicalSize::new(width, height - 5.0));
         self.notes.resize(target, self.drum_roll.lanes());
-        self.marks
-            .resize(target, self.drum_roll.lanes(), &self.played_notes);
+        self.marks.resize(
+            target,
+            self.drum_roll.lanes(),
+            &self.played_notes,
+            &self.choked_notes,
+        );
     }
 
     fn update(&mut self, target: &mut Target, delta: Duration) {
         let wait_for_notes = self.player.wait_for_notes();
         if wait_for_notes.are_required_keys_pressed() || !target.config.wait_for_notes {
             self.player.update(target, delta);
+        } else {
+            self.player.hold(target);
         }
 
         if self.player.percentage() >= 1.0 {
@@ -208,6 +237,9 @@ impl Scene for PlayingScene {
         self.quad_pipeline
             .render(&target.transform_uniform, &mut render_pass);
 
+        self.drum_roll
+            .render_meters(&target.transform_uniform, &mut render_pass);
+
         self.marks
             .render(&target.transform_uniform, &mut render_pass);
     }
@@ -218,6 +250,14 @@ impl Scene for PlayingScene {
 
         match &event {
             KeyboardInput { input, .. } => {
+                // Keys bound to drums are not used for anything else
+                if self.keyboard_drums.is_bound(target, input) {
+                    if let Some(event) = self.keyboard_drums.keyboard_input(target, input) {
+                        self.midi_event(target, &event);
+                    }
+                    return;
+                }
+
                 self.player.keyboard_input(input);
 
                 settings_keyboard_input(target, &mut self.toast_manager, input);
@@ -236,6 +276,11 @@ impl Scene for PlayingScene {
             }
             MouseInput { state, button, .. } => {
                 self.player.mouse_input(target, state, button);
+
+                let lanes = self.drum_roll.lanes();
+                if let Some(event) = self.keyboard_drums.mouse_input(target, lanes, state, button) {
+                    self.midi_event(target, &event);
+                }
             }
             CursorMoved { position, .. } => {
                 self.player.handle_cursor_moved(target, position);
@@ -244,9 +289,52 @@ impl Scene for PlayingScene {
         }
     }
 
+    fn clock_event(&mut self, target: &mut Target, event: &ClockEvent) {
+        self.player.clock_event(target, event);
+    }
+
+    fn port_event(&mut self, _target: &mut Target, event: &PortEvent) {
+        self.toast_manager.toast(event.to_string());
+    }
+
     fn midi_event(&mut self, target: &mut Target, event: &MidiEvent) {
         match event.message {
-            MidiMessage::NoteOn { key, .. } => {
+            MidiMessage::Controller { controller, value } if controller == HIHAT_PEDAL_CC => {
+                self.hihat.set_value(value.as_int());
+                self.drum_roll.set_hihat_openness(self.hihat.openness());
+
+                self.player
+                    .output_manager
+                    .borrow_mut()
+                    .midi_event(OutputGroup::UserDrums, DRUM_CHANNEL, event.message);
+            }
+            MidiMessage::NoteOn { key, vel } => {
+                let key = self.hihat.note_on(key);
+                let mut message = MidiMessage::NoteOn { key, vel };
+                let instant = target.input_manager.instant(event);
+
+                if let Some(mapping) = get_midi_mapping_for_note(key.as_int()) {
+                    let accepted = self.input_filter.accept(
+                        &target.config.input_filter,
+                        &mapping,
+                        vel.as_int(),
+                        instant,
+                    );
+
+                    if !accepted {
+                        return;
+                    }
+
+                    let vel = target
+                        .config
+                        .velocity_curve(mapping.id)
+                        .apply(vel.as_int());
+                    message = MidiMessage::NoteOn {
+                        key,
+                        vel: vel.into(),
+                    };
+                }
+
                 self.player.wait_for_notes().press_key(
                     midi_player::KeyPressSource::User,
                     key.as_int(),
@@ -258,29 +346,69 @@ impl Scene for PlayingScene {
                         self.player
                             .output_manager
                             .borrow_mut()
-                            .midi_event(event.channel, event.message);
+                            .midi_event(OutputGroup::UserDrums, DRUM_CHANNEL, message);
                     }
 
-                    self.played_notes.push((
-                        self.player.time_without_lead_in() + target.config.playback_offset,
-                        mapping,
-                    ));
+                    let time = self.player.time_without_lead_in_at(instant);
+
+                    self.played_notes
+                        .push((time + target.config.playback_offset, mapping));
 
-                    self.marks
-                        .resize(target, self.drum_roll.lanes(), &self.played_notes);
+                    self.marks.resize(
+                        target,
+                        self.drum_roll.lanes(),
+                        &self.played_notes,
+                        &self.choked_notes,
+                    );
                 }
             }
-            MidiMessage::NoteOff { key, .. } => {
+            MidiMessage::NoteOff { key, vel } => {
+                let key = self.hihat.note_off(key);
+                let message = MidiMessage::NoteOff { key, vel };
+
                 self.player
                     .output_manager
                     .borrow_mut()
-                    .midi_event(event.channel, event.message);
+                    .midi_event(OutputGroup::UserDrums, DRUM_CHANNEL, message);
                 self.player.wait_for_notes().press_key(
                     midi_player::KeyPressSource::User,
                     key.as_int(),
                     false,
                 );
             }
+            MidiMessage::Aftertouch { key, vel } => {
+                if !target.config.mute_drums {
+                    self.player
+                        .output_manager
+                        .borrow_mut()
+                        .midi_event(OutputGroup::UserDrums, DRUM_CHANNEL, event.message);
+                }
+
+                if vel == 0 {
+                    self.choked_keys.remove(&key.as_int());
+                    return;
+                }
+
+                // Kits keep sending pressure while the cymbal is held, only the grab is marked
+                if !self.choked_keys.insert(key.as_int()) {
+                    return;
+                }
+
+                if let Some(mapping) = get_midi_mapping_for_note(key.as_int()) {
+                    let instant = target.input_manager.instant(event);
+                    let time = self.player.time_without_lead_in_at(instant);
+
+                    self.choked_notes
+                        .push((time + target.config.playback_offset, mapping));
+
+                    self.marks.resize(
+                        target,
+                        self.drum_roll.lanes(),
+                        &self.played_notes,
+                        &self.choked_notes,
+                    );
+                }
+            }
             _ => {}
         }
     }
@@ -1,7 +1,11 @@
+use std::collections::HashSet;
+
+use crate::config::default_color_schema;
 use crate::config::PlayingSceneLayout;
 use crate::target::Target;
 use crate::TransformUniform;
 use crate::Uniform;
+use midly::MidiMessage;
 use neothesia_pipelines::waterfall::{NoteInstance, WaterfallPipeline};
 use wgpu_jumpstart::Color;
 
@@ -15,10 +19,12 @@ pub struct Notes {
 impl Notes {
     pub fn new(target: &mut Target, lanes: &[Lane]) -> Self {
         let is_vertical_layout = target.config.layout == PlayingSceneLayout::Vertical;
+        let midi = target.midi_file.as_ref().unwrap();
         let notes_pipeline = WaterfallPipeline::new(
             &target.gpu,
             &target.transform_uniform,
-            target.midi_file.as_ref().unwrap().merged_track.notes.len(),
+            // Every note and choke is backed by at least one event, every grid line by a click
+            midi.merged_track.events.len() + midi.metronome.clicks.len(),
             is_vertical_layout,
         );
         let mut notes = Self {
@@ -36,33 +42,15 @@ impl Notes {
         if !self.is_vertical_layout {
             let lane = lanes.first().unwrap();
             let metronome_grid = [0.05, 0.05, 0.05];
-            let metronome_events = midi
-                .merged_track
-                .events
-                .iter()
-                .filter(|n| n.channel == 15);
-
-            for (i, event) in metronome_events.clone().enumerate() {
-                let ref_event = if i == 0 {
-                    metronome_events.clone().next()
-                } else {
-                    metronome_events.clone().nth(i - 1)
-                };
-
-                if ref_event.is_none() {
-                    log::error!("failed to find metronome events");
-                    continue;
-                }
-
-                let ref_event = ref_event.unwrap();
 
-                let grid_size = if i == 0 {
-                    (ref_event.timestamp - event.timestamp).as_secs_f32()
-                } else {
-                    (event.timestamp - ref_event.timestamp).as_secs_f32()
+            let clicks = &midi.metronome.clicks;
+            for (i, click) in clicks.iter().enumerate() {
+                let grid_size = match i.checked_sub(1).map(|prev| &clicks[prev]) {
+                    Some(prev) => (click.timestamp - prev.timestamp).as_secs_f32(),
+                    None => 0.0,
                 };
 
-                let x = event.timestamp.as_secs_f32() - grid_size * 2.0;
+                let x = click.timestamp.as_secs_f32() - grid_size * 2.0;
                 let h = lanes.len() as f32 * lane.size.h;
 
                 instances.push(NoteInstance {
@@ -112,6 +100,52 @@ impl Notes {
             }
         }
 
+        // Cymbal chokes encoded as polyphonic aftertouch
+        let mut choked = HashSet::new();
+        for event in midi
+            .merged_track
+            .events
+            .iter()
+            .filter(|e| e.channel == 9)
+        {
+            let (key, vel) = match event.message {
+                MidiMessage::Aftertouch { key, vel } => (key.as_int(), vel.as_int()),
+                _ => continue,
+            };
+
+            if vel == 0 {
+                choked.remove(&key);
+                continue;
+            }
+
+            // Only the grab starts a choke, pressure updates while holding are ignored
+            if !choked.insert(key) {
+                continue;
+            }
+
+            if let Some(lane) = lanes.iter().find(|i| i.mapping.accept_note(key)) {
+                let color: Color = default_color_schema().beige.into();
+                let mut w = 3.0;
+                let mut h = f32::min(lane.size.h * 0.6, 100.0);
+                let mut x = event.timestamp.as_secs_f32();
+                let mut y = lane.pos.y + (lane.size.h / 2.0) - (h / 2.0);
+
+                if self.is_vertical_layout {
+                    w = f32::min(lane.size.w * 0.6, 100.0);
+                    h = 3.0;
+                    x = lane.pos.x + (lane.size.w / 2.0) - (w / 2.0);
+                    y = event.timestamp.as_secs_f32();
+                }
+
+                instances.push(NoteInstance {
+                    position: [x, y],
+                    size: [w, h],
+                    color: color.into_linear_rgb(),
+                    radius: 0.0,
+                });
+            }
+        }
+
         self.notes_pipeline
             .update_instance_buffer(&mut target.gpu, instances);
     }
@@ -1,6 +1,8 @@
 use std::time::Duration;
 
 use crate::{
+    input_manager::ClockEvent,
+    port_watcher::PortEvent,
     scene::{Scene, SceneType},
     target::Target,
 };
@@ -52,6 +54,14 @@ impl SceneManager {
         self.scene.midi_event(target, event)
     }
 
+    pub fn clock_event(&mut self, target: &mut Target, event: &ClockEvent) {
+        self.scene.clock_event(target, event)
+    }
+
+    pub fn port_event(&mut self, target: &mut Target, event: &PortEvent) {
+        self.scene.port_event(target, event)
+    }
+
     pub fn main_events_cleared(&mut self, target: &mut Target) {
         self.scene.main_events_cleared(target)
     }
@@ -2,7 +2,8 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use crate::config::Config;
-use crate::input_manager::InputManager;
+use crate::input_manager::{InputManager, InputRole};
+use crate::port_watcher::{self, PortEvent};
 use crate::ui::TextRenderer;
 use crate::utils::window::WindowState;
 use crate::{EventLoopProxy, OutputManager, TransformUniform};
@@ -71,7 +72,17 @@ impl Target {
             None
         };
 
-        Self {
+        port_watcher::spawn(proxy.clone());
+
+        let config = Config::new();
+        let output_manager = Rc::new(RefCell::new(OutputManager::new(&config.audio)));
+        #[cfg(feature = "synth")]
+        output_manager
+            .borrow_mut()
+            .set_synth_effects(&config.synth_effects);
+
+        #[allow(unused_mut)]
+        let mut target = Self {
             #[cfg(feature = "app")]
             window,
             #[cfg(feature = "app")]
@@ -83,11 +94,64 @@ impl Target {
 
             text_renderer,
 
-            output_manager: Default::default(),
+            output_manager,
             input_manager: InputManager::new(proxy.clone()),
             midi_file,
-            config: Config::new(),
+            config,
             proxy,
+        };
+
+        #[cfg(target_os = "linux")]
+        target.update_virtual_ports();
+
+        target
+    }
+
+    /// Publish the virtual MIDI ports when they are enabled in the config, remove them otherwise.
+    #[cfg(target_os = "linux")]
+    pub fn update_virtual_ports(&mut self) {
+        let enabled = self.config.virtual_ports;
+        self.input_manager.set_virtual_input(enabled);
+        self.output_manager.borrow_mut().set_virtual_output(enabled);
+    }
+
+    /// Reconnect to the ports stored in the config when their device is plugged in.
+    pub fn port_event(&mut self, event: &PortEvent) {
+        match event {
+            PortEvent::InputConnected(port) => {
+                let name = port.to_string();
+                let role = InputRole {
+                    drums: self.config.inputs.contains(&name),
+                    clock: self.config.clock_input.as_ref() == Some(&name),
+                };
+
+                if role != InputRole::default() {
+                    self.input_manager.connect_input(port.clone(), role);
+                }
+            }
+            PortEvent::InputDisconnected(port) => {
+                self.input_manager.disconnect_input(port);
+            }
+            PortEvent::OutputConnected(port) => {
+                let name = port.to_string();
+                let mut output = self.output_manager.borrow_mut();
+
+                output.reconnect_midi_port(&name);
+
+                // The device might have been missing when the song started
+                if self.config.output.as_ref() == Some(&name) {
+                    if let Some(desc) = output.midi_port(&name) {
+                        output.connect(desc);
+                    }
+                }
+                for (group, route) in self.config.output_routes.iter() {
+                    if route == &name {
+                        let desc = output.midi_port(&name);
+                        output.connect_route(*group, desc);
+                    }
+                }
+            }
+            PortEvent::OutputDisconnected(_) => {}
         }
     }
 
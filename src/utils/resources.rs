@@ -51,6 +51,42 @@ pub fn settings_ron() -> Option<PathBuf> {
     return bundled_resource_path("settings", "ron").map(PathBuf::from);
 }
 
+/// Directory scanned for user defined output note maps (`*.ron`).
+pub fn note_maps_dir() -> Option<PathBuf> {
+    #[cfg(all(target_family = "unix", not(target_os = "macos")))]
+    return xdg_config().map(|p| p.join("note_maps"));
+
+    #[cfg(target_os = "windows")]
+    return Some(PathBuf::from("./note_maps"));
+
+    #[cfg(target_os = "macos")]
+    return std::env::var_os("HOME").map(|h| {
+        PathBuf::from(h)
+            .join("Library")
+            .join("Application Support")
+            .join("Drumsthesia")
+            .join("note_maps")
+    });
+}
+
+/// Directory where recorded sessions (`*.wav`) are saved.
+pub fn sessions_dir() -> Option<PathBuf> {
+    #[cfg(all(target_family = "unix", not(target_os = "macos")))]
+    return xdg_config().map(|p| p.join("sessions"));
+
+    #[cfg(target_os = "windows")]
+    return Some(PathBuf::from("./sessions"));
+
+    #[cfg(target_os = "macos")]
+    return std::env::var_os("HOME").map(|h| {
+        PathBuf::from(h)
+            .join("Library")
+            .join("Application Support")
+            .join("Drumsthesia")
+            .join("sessions")
+    });
+}
+
 #[cfg(target_os = "macos")]
 fn bundled_resource_path(name: &str, extension: &str) -> Option<String> {
     use objc::runtime::{Class, Object};